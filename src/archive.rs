@@ -0,0 +1,150 @@
+// Reads the files of a `.crate` or `.tar.gz` archive into memory. Only
+// regular files are kept, along with the directories they imply, and the
+// long names of GNU and PAX headers are followed.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::inflate;
+
+const BLOCK: usize = 512;
+
+pub struct Archive {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+// Whether an input names an archive rather than a source file or directory.
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".crate") || name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Archive, String> {
+        let error = |message: String| format!("{}: {}", path.display(), message);
+        let data = fs::read(path).map_err(|err| error(err.to_string()))?;
+        let tar = inflate::gunzip(&data).map_err(error)?;
+        Archive::from_tar(&tar).map_err(error)
+    }
+
+    fn from_tar(tar: &[u8]) -> Result<Archive, String> {
+        let mut archive = Archive {
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+        };
+        let mut long_name = None;
+        let mut pos = 0;
+        while pos + BLOCK <= tar.len() {
+            let header = &tar[pos..pos + BLOCK];
+            // The archive ends with two zero blocks, but one is enough.
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+            let size = octal(&header[124..136]).ok_or("invalid tar entry size")?;
+            let start = pos + BLOCK;
+            let data = tar.get(start..start + size).ok_or("truncated tar entry")?;
+            pos = start + size.div_ceil(BLOCK) * BLOCK;
+
+            match header[156] {
+                b'L' => {
+                    long_name = Some(string(data));
+                    continue;
+                }
+                b'x' => {
+                    long_name = pax_path(data).or(long_name);
+                    continue;
+                }
+                _ => {}
+            }
+            let name = match long_name.take() {
+                Some(name) => name,
+                None => {
+                    let name = string(&header[..100]);
+                    let prefix = string(&header[345..500]);
+                    // Only ustar headers have a prefix field.
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{}/{}", prefix, name)
+                    } else {
+                        name
+                    }
+                }
+            };
+            if matches!(header[156], b'0' | 0) {
+                if let Some(path) = relative(&name) {
+                    archive.add(path, data.to_vec());
+                }
+            }
+        }
+        Ok(archive)
+    }
+
+    fn add(&mut self, path: PathBuf, data: Vec<u8>) {
+        for dir in path.ancestors().skip(1) {
+            if !self.dirs.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
+        self.files.insert(path, data);
+    }
+
+    pub fn file(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(path).map(Vec::as_slice)
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    // The files and directories directly within `dir`.
+    pub fn entries(&self, dir: &Path) -> Vec<PathBuf> {
+        let files = self.files.keys();
+        let dirs = self.dirs.iter().filter(|path| !path.as_os_str().is_empty());
+        files
+            .chain(dirs)
+            .filter(|path| path.parent() == Some(dir))
+            .cloned()
+            .collect()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+}
+
+fn string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn octal(field: &[u8]) -> Option<usize> {
+    let digits = string(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(digits, 8).ok()
+}
+
+// PAX extended headers are records of the form `<length> <key>=<value>\n`.
+fn pax_path(data: &[u8]) -> Option<String> {
+    let records = String::from_utf8_lossy(data);
+    records.lines().find_map(|record| {
+        let (_, record) = record.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+// Entries escaping the archive through `..` or absolute paths are dropped.
+fn relative(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
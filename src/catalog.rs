@@ -0,0 +1,216 @@
+// Unsafe functions and methods declared outside of the analysed crate: a
+// built-in list for std, core and alloc, extended by the `[unsafe]` section
+// of the configuration file:
+//
+//     [unsafe]
+//     functions = ["libc::*", "raw_buf::RawBuf::from_parts"]
+//     methods = ["get_raw_unchecked"]
+
+use std::collections::HashMap;
+
+use crate::config;
+use crate::symbols::Callee;
+use crate::toml;
+
+// Matched against the end of a call path, so `ptr::read` covers
+// `std::ptr::read`, `core::ptr::read` and `ptr::read` after `use std::ptr`.
+const FUNCTIONS: &[&str] = &[
+    "alloc::alloc",
+    "alloc::alloc_zeroed",
+    "alloc::dealloc",
+    "alloc::realloc",
+    "char::from_u32_unchecked",
+    "hint::assert_unchecked",
+    "hint::unreachable_unchecked",
+    "intrinsics::*",
+    "mem::transmute",
+    "mem::transmute_copy",
+    "mem::uninitialized",
+    "mem::zeroed",
+    "ptr::copy",
+    "ptr::copy_nonoverlapping",
+    "ptr::drop_in_place",
+    "ptr::read",
+    "ptr::read_unaligned",
+    "ptr::read_volatile",
+    "ptr::replace",
+    "ptr::swap",
+    "ptr::swap_nonoverlapping",
+    "ptr::write",
+    "ptr::write_bytes",
+    "ptr::write_unaligned",
+    "ptr::write_volatile",
+    "slice::from_raw_parts",
+    "slice::from_raw_parts_mut",
+    "str::from_utf8_unchecked",
+    "str::from_utf8_unchecked_mut",
+    "Arc::decrement_strong_count",
+    "Arc::from_raw",
+    "Arc::increment_strong_count",
+    "Box::from_raw",
+    "CStr::from_bytes_with_nul_unchecked",
+    "CStr::from_ptr",
+    "CString::from_raw",
+    "CString::from_vec_unchecked",
+    "ManuallyDrop::drop",
+    "ManuallyDrop::take",
+    "NonNull::new_unchecked",
+    "NonZero::new_unchecked",
+    "OsStr::from_encoded_bytes_unchecked",
+    "OsString::from_encoded_bytes_unchecked",
+    "Pin::new_unchecked",
+    "Rc::decrement_strong_count",
+    "Rc::from_raw",
+    "Rc::increment_strong_count",
+    "String::from_raw_parts",
+    "String::from_utf8_unchecked",
+    "Vec::from_raw_parts",
+];
+
+const TRAIT_FUNCTIONS: &[&str] = &[
+    "GlobalAlloc::alloc",
+    "GlobalAlloc::alloc_zeroed",
+    "GlobalAlloc::dealloc",
+    "GlobalAlloc::realloc",
+];
+
+// Methods whose names are specific enough to be matched on any receiver.
+const METHODS: &[&str] = &[
+    "as_bytes_mut",
+    "as_mut_vec",
+    "assume_init",
+    "assume_init_drop",
+    "assume_init_mut",
+    "assume_init_read",
+    "assume_init_ref",
+    "get_unchecked",
+    "get_unchecked_mut",
+    "map_unchecked",
+    "map_unchecked_mut",
+    "set_len",
+    "to_int_unchecked",
+    "unchecked_add",
+    "unchecked_mul",
+    "unchecked_shl",
+    "unchecked_shr",
+    "unchecked_sub",
+    "unwrap_unchecked",
+];
+
+const TRAIT_METHODS: &[&str] = &["from_raw_fd", "from_raw_handle", "from_raw_socket"];
+
+// Raw pointer methods, which share their names with common safe methods and
+// are only matched when the receiver is known to be a raw pointer.
+const POINTER_METHODS: &[&str] = &[
+    "add",
+    "as_mut",
+    "as_ref",
+    "as_uninit_mut",
+    "as_uninit_ref",
+    "byte_add",
+    "byte_offset",
+    "byte_offset_from",
+    "byte_sub",
+    "copy_from",
+    "copy_from_nonoverlapping",
+    "copy_to",
+    "copy_to_nonoverlapping",
+    "drop_in_place",
+    "offset",
+    "offset_from",
+    "read",
+    "read_unaligned",
+    "read_volatile",
+    "replace",
+    "sub",
+    "swap",
+    "write",
+    "write_bytes",
+    "write_unaligned",
+    "write_volatile",
+];
+
+pub struct Catalog {
+    // Path suffixes, where a final `*` matches any function in a module.
+    functions: Vec<(Vec<String>, Callee)>,
+    methods: HashMap<String, Callee>,
+}
+
+impl Catalog {
+    pub fn new(config: Option<&toml::Table>) -> Result<Catalog, String> {
+        let mut catalog = Catalog {
+            functions: Vec::new(),
+            methods: HashMap::new(),
+        };
+        for function in FUNCTIONS {
+            catalog.add_function(function, Callee::Unsafe);
+        }
+        for function in TRAIT_FUNCTIONS {
+            catalog.add_function(function, Callee::UnsafeTraitMethod);
+        }
+        for method in METHODS {
+            catalog.methods.insert(method.to_string(), Callee::Unsafe);
+        }
+        for method in TRAIT_METHODS {
+            let callee = Callee::UnsafeTraitMethod;
+            catalog.methods.insert(method.to_string(), callee);
+        }
+
+        let section = match config.and_then(|config| config.get("unsafe")) {
+            Some(section) => section
+                .as_table()
+                .ok_or("`unsafe` must be a table of function and method lists")?,
+            None => return Ok(catalog),
+        };
+        if let Some(key) = section
+            .keys()
+            .find(|key| *key != "functions" && *key != "methods")
+        {
+            return Err(format!("unknown key `unsafe.{}`", key));
+        }
+        for function in config::strings(section, "unsafe", "functions")? {
+            if function.is_empty() || function.split("::").any(str::is_empty) {
+                return Err(format!("invalid function path `{}`", function));
+            }
+            catalog.add_function(&function, Callee::Unsafe);
+        }
+        for method in config::strings(section, "unsafe", "methods")? {
+            catalog.methods.insert(method, Callee::Unsafe);
+        }
+        Ok(catalog)
+    }
+
+    fn add_function(&mut self, path: &str, callee: Callee) {
+        let segments = path.split("::").map(str::to_string).collect();
+        self.functions.push((segments, callee));
+    }
+
+    // Matches any of the ways a call path can be spelled, as returned by
+    // `SymbolTable::external_paths`.
+    pub fn resolve_call(&self, paths: &[Vec<String>]) -> Option<Callee> {
+        paths.iter().find_map(|path| {
+            self.functions
+                .iter()
+                .find(|(pattern, _)| matches(pattern, path))
+                .map(|(_, callee)| *callee)
+        })
+    }
+
+    pub fn resolve_method(&self, name: &str, ptr_receiver: bool) -> Option<Callee> {
+        if ptr_receiver && POINTER_METHODS.contains(&name) {
+            return Some(Callee::Unsafe);
+        }
+        self.methods.get(name).copied()
+    }
+}
+
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    if path.len() < pattern.len() {
+        return false;
+    }
+    let suffix = &path[path.len() - pattern.len()..];
+    pattern
+        .iter()
+        .zip(suffix)
+        .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+}
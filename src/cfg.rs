@@ -0,0 +1,553 @@
+// Evaluation of `#[cfg]` and `#[cfg_attr]` against the cfg set given on the
+// command line. Predicates about something that was not specified, such as
+// `windows` when no target was given, are unknown and the code they guard is
+// analysed as if it were compiled.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, ForeignItem, ImplItem, Item, Lit, Meta, Token, TraitItem};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cfg {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    fn from_meta(meta: &Meta) -> Option<Cfg> {
+        match meta {
+            Meta::Path(path) => Some(Cfg::Name(path.get_ident()?.to_string())),
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(value),
+                    ..
+                }) => Some(Cfg::KeyValue(
+                    meta.path.get_ident()?.to_string(),
+                    value.value(),
+                )),
+                _ => None,
+            },
+            Meta::List(list) => {
+                let nested = list
+                    .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                    .ok()?;
+                let nested: Vec<Cfg> = nested.iter().map(Cfg::from_meta).collect::<Option<_>>()?;
+                match list.path.get_ident()?.to_string().as_str() {
+                    "all" => Some(Cfg::All(nested)),
+                    "any" => Some(Cfg::Any(nested)),
+                    "not" if nested.len() == 1 => {
+                        Some(Cfg::Not(Box::new(nested.into_iter().next()?)))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // The conjunction of several predicates, flattened.
+    pub fn all(cfgs: &[Cfg]) -> Cfg {
+        let mut all = Vec::new();
+        for cfg in cfgs {
+            match cfg {
+                Cfg::All(nested) => all.extend(nested.iter().cloned()),
+                cfg => all.push(cfg.clone()),
+            }
+        }
+        if all.len() == 1 {
+            return all.pop().unwrap();
+        }
+        Cfg::All(all)
+    }
+}
+
+impl Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter, name: &str, cfgs: &[Cfg]| {
+            let cfgs: Vec<String> = cfgs.iter().map(Cfg::to_string).collect();
+            write!(f, "{}({})", name, cfgs.join(", "))
+        };
+        match self {
+            Cfg::Name(name) => f.write_str(name),
+            Cfg::KeyValue(key, value) => write!(f, "{} = {:?}", key, value),
+            Cfg::All(cfgs) => list(f, "all", cfgs),
+            Cfg::Any(cfgs) => list(f, "any", cfgs),
+            Cfg::Not(cfg) => write!(f, "not({})", cfg),
+        }
+    }
+}
+
+// Names that describe the target rather than the build configuration.
+const TARGET_NAMES: &[&str] = &["unix", "windows"];
+
+// Names that are set in a default `cargo build`.
+const DEFAULT_NAMES: &[&str] = &["debug_assertions"];
+
+const UNIX_OSES: &[&str] = &[
+    "linux",
+    "macos",
+    "ios",
+    "android",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "haiku",
+    "fuchsia",
+    "redox",
+];
+
+#[derive(Clone, Default)]
+pub struct CfgSet {
+    names: HashSet<String>,
+    values: HashSet<(String, String)>,
+    // Keys whose values are all given, such that any other value is false.
+    known_keys: HashSet<String>,
+    // Whether any cfg was given at all; if not, nothing is known.
+    enabled: bool,
+    target: bool,
+    all_features: bool,
+}
+
+impl CfgSet {
+    // Parses `name`, `key="value"` or `key=value`.
+    pub fn add_cfg(&mut self, spec: &str) -> Result<(), String> {
+        self.enabled = true;
+        match spec.split_once('=') {
+            Some((key, value)) => {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                let key = key.trim();
+                if key.is_empty() {
+                    return Err(format!("invalid cfg `{}`", spec));
+                }
+                if key == "target_os" {
+                    self.set_target_os(value);
+                    return Ok(());
+                }
+                if key == "target_family" && TARGET_NAMES.contains(&value) {
+                    self.set_family(value);
+                }
+                self.known_keys.insert(key.to_string());
+                self.values.insert((key.to_string(), value.to_string()));
+            }
+            None if spec.trim().is_empty() => return Err(format!("invalid cfg `{}`", spec)),
+            None if TARGET_NAMES.contains(&spec.trim()) => self.set_family(spec.trim()),
+            None => {
+                self.names.insert(spec.trim().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // Naming `unix` or `windows` selects a target of that family.
+    fn set_family(&mut self, family: &str) {
+        self.target = true;
+        self.names.insert(family.to_string());
+        self.known_keys.insert("target_family".to_string());
+        self.values
+            .insert(("target_family".to_string(), family.to_string()));
+    }
+
+    // Evaluates predicates from now on, even with nothing else given.
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn add_feature(&mut self, feature: &str) {
+        self.enabled = true;
+        self.known_keys.insert("feature".to_string());
+        self.values
+            .insert(("feature".to_string(), feature.to_string()));
+    }
+
+    pub fn enable_all_features(&mut self) {
+        self.enabled = true;
+        self.all_features = true;
+    }
+
+    // Selects a target OS, which also determines `unix`, `windows` and
+    // `target_family`.
+    pub fn set_target_os(&mut self, os: &str) {
+        self.enabled = true;
+        self.target = true;
+        let family = if os == "windows" {
+            Some("windows")
+        } else if UNIX_OSES.contains(&os) {
+            Some("unix")
+        } else {
+            None
+        };
+        for key in ["target_os", "target_family"] {
+            self.known_keys.insert(key.to_string());
+        }
+        self.values
+            .insert(("target_os".to_string(), os.to_string()));
+        if let Some(family) = family {
+            self.set_family(family);
+        }
+    }
+
+    pub fn set_target_arch(&mut self, arch: &str) {
+        self.enabled = true;
+        let pointer_width = match arch {
+            "x86_64" | "aarch64" | "powerpc64" | "mips64" | "riscv64" | "s390x" | "loongarch64" => {
+                Some("64")
+            }
+            "x86" | "arm" | "powerpc" | "mips" | "riscv32" | "wasm32" => Some("32"),
+            _ => None,
+        };
+        self.known_keys.insert("target_arch".to_string());
+        self.values
+            .insert(("target_arch".to_string(), arch.to_string()));
+        if let Some(width) = pointer_width {
+            self.known_keys.insert("target_pointer_width".to_string());
+            self.values
+                .insert(("target_pointer_width".to_string(), width.to_string()));
+        }
+        if arch.starts_with("wasm") {
+            self.known_keys.insert("target_family".to_string());
+            self.values
+                .insert(("target_family".to_string(), "wasm".to_string()));
+        }
+    }
+
+    // The set for a package whose `[features]` table is `features`: the
+    // requested features, the default ones unless disabled, and everything
+    // they enable. Features may be prefixed with the package name.
+    pub fn for_package(
+        &self,
+        package: &str,
+        features: &HashMap<String, Vec<String>>,
+        default_features: bool,
+    ) -> CfgSet {
+        if !self.enabled || self.all_features {
+            return self.clone();
+        }
+        let mut set = self.clone();
+        let mut pending: Vec<String> = self
+            .values
+            .iter()
+            .filter(|(key, _)| key == "feature")
+            .map(|(_, feature)| feature.clone())
+            .filter_map(|feature| match feature.split_once('/') {
+                Some((name, feature)) if name == package => Some(feature.to_string()),
+                Some(_) => None,
+                None => Some(feature),
+            })
+            .collect();
+        set.values.retain(|(key, _)| key != "feature");
+        if default_features && features.contains_key("default") {
+            pending.push("default".to_string());
+        }
+        while let Some(feature) = pending.pop() {
+            if !set.values.insert(("feature".to_string(), feature.clone())) {
+                continue;
+            }
+            // `dep:name` and `name/feature` refer to dependencies, and
+            // `name?/feature` only applies when they are otherwise enabled.
+            let enables = features.get(&feature).into_iter().flatten();
+            pending.extend(
+                enables
+                    .filter(|enabled| !enabled.contains(['/', ':']))
+                    .cloned(),
+            );
+        }
+        set
+    }
+
+    // `Some(false)` for predicates known to be false, `None` for those that
+    // depend on something not specified.
+    pub fn eval(&self, cfg: &Cfg) -> Option<bool> {
+        if !self.enabled {
+            return None;
+        }
+        match cfg {
+            Cfg::Name(name) if self.names.contains(name) => Some(true),
+            Cfg::Name(name) if TARGET_NAMES.contains(&name.as_str()) && !self.target => None,
+            Cfg::Name(name) => Some(DEFAULT_NAMES.contains(&name.as_str())),
+            Cfg::KeyValue(key, _) if key == "feature" && self.all_features => Some(true),
+            Cfg::KeyValue(key, value) => {
+                let pair = (key.clone(), value.clone());
+                if self.values.contains(&pair) {
+                    Some(true)
+                } else if self.known_keys.contains(key) || key == "feature" {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Cfg::All(cfgs) => {
+                let values: Vec<Option<bool>> = cfgs.iter().map(|cfg| self.eval(cfg)).collect();
+                if values.contains(&Some(false)) {
+                    Some(false)
+                } else if values.contains(&None) {
+                    None
+                } else {
+                    Some(true)
+                }
+            }
+            Cfg::Any(cfgs) => {
+                let values: Vec<Option<bool>> = cfgs.iter().map(|cfg| self.eval(cfg)).collect();
+                if values.contains(&Some(true)) {
+                    Some(true)
+                } else if values.contains(&None) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            Cfg::Not(cfg) => self.eval(cfg).map(|value| !value),
+        }
+    }
+
+    // The `cfg` predicates of a node, including those added by `cfg_attr`.
+    pub fn predicates(&self, attrs: &[Attribute]) -> Vec<Cfg> {
+        let mut cfgs = Vec::new();
+        self.each_attr(attrs, |meta| {
+            if let Meta::List(list) = meta {
+                if list.path.is_ident("cfg") {
+                    let cfg = list.parse_args::<Meta>().ok();
+                    cfgs.extend(cfg.as_ref().and_then(Cfg::from_meta));
+                }
+            }
+        });
+        cfgs
+    }
+
+    // Whether a node with these attributes may be compiled.
+    pub fn is_active(&self, attrs: &[Attribute]) -> bool {
+        !self
+            .predicates(attrs)
+            .iter()
+            .any(|cfg| self.eval(cfg) == Some(false))
+    }
+
+    // Calls `f` with each attribute that applies, with each `cfg_attr`
+    // replaced by the attributes it carries unless its predicate is known
+    // to be false.
+    pub fn each_attr<F>(&self, attrs: &[Attribute], mut f: F)
+    where
+        F: FnMut(&Meta),
+    {
+        for attr in attrs {
+            self.expand(&attr.meta, &mut f);
+        }
+    }
+
+    fn expand(&self, meta: &Meta, f: &mut dyn FnMut(&Meta)) {
+        let list = match meta {
+            Meta::List(list) if list.path.is_ident("cfg_attr") => list,
+            _ => return f(meta),
+        };
+        let args = match list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) {
+            Ok(args) => args,
+            Err(_) => return,
+        };
+        let predicate = args.first().and_then(Cfg::from_meta);
+        if predicate.is_some_and(|cfg| self.eval(&cfg) == Some(false)) {
+            return;
+        }
+        for meta in args.iter().skip(1) {
+            self.expand(meta, f);
+        }
+    }
+}
+
+pub fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn impl_item_attrs(item: &ImplItem) -> &[Attribute] {
+    match item {
+        ImplItem::Const(item) => &item.attrs,
+        ImplItem::Fn(item) => &item.attrs,
+        ImplItem::Type(item) => &item.attrs,
+        ImplItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn trait_item_attrs(item: &TraitItem) -> &[Attribute] {
+    match item {
+        TraitItem::Const(item) => &item.attrs,
+        TraitItem::Fn(item) => &item.attrs,
+        TraitItem::Type(item) => &item.attrs,
+        TraitItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn foreign_item_attrs(item: &ForeignItem) -> &[Attribute] {
+    match item {
+        ForeignItem::Fn(item) => &item.attrs,
+        ForeignItem::Static(item) => &item.attrs,
+        ForeignItem::Type(item) => &item.attrs,
+        ForeignItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+pub fn expr_attrs(expr: &Expr) -> &[Attribute] {
+    match expr {
+        Expr::Array(expr) => &expr.attrs,
+        Expr::Assign(expr) => &expr.attrs,
+        Expr::Async(expr) => &expr.attrs,
+        Expr::Await(expr) => &expr.attrs,
+        Expr::Binary(expr) => &expr.attrs,
+        Expr::Block(expr) => &expr.attrs,
+        Expr::Break(expr) => &expr.attrs,
+        Expr::Call(expr) => &expr.attrs,
+        Expr::Cast(expr) => &expr.attrs,
+        Expr::Closure(expr) => &expr.attrs,
+        Expr::Const(expr) => &expr.attrs,
+        Expr::Continue(expr) => &expr.attrs,
+        Expr::Field(expr) => &expr.attrs,
+        Expr::ForLoop(expr) => &expr.attrs,
+        Expr::Group(expr) => &expr.attrs,
+        Expr::If(expr) => &expr.attrs,
+        Expr::Index(expr) => &expr.attrs,
+        Expr::Infer(expr) => &expr.attrs,
+        Expr::Let(expr) => &expr.attrs,
+        Expr::Lit(expr) => &expr.attrs,
+        Expr::Loop(expr) => &expr.attrs,
+        Expr::Macro(expr) => &expr.attrs,
+        Expr::Match(expr) => &expr.attrs,
+        Expr::MethodCall(expr) => &expr.attrs,
+        Expr::Paren(expr) => &expr.attrs,
+        Expr::Path(expr) => &expr.attrs,
+        Expr::Range(expr) => &expr.attrs,
+        Expr::Reference(expr) => &expr.attrs,
+        Expr::Repeat(expr) => &expr.attrs,
+        Expr::Return(expr) => &expr.attrs,
+        Expr::Struct(expr) => &expr.attrs,
+        Expr::Try(expr) => &expr.attrs,
+        Expr::TryBlock(expr) => &expr.attrs,
+        Expr::Tuple(expr) => &expr.attrs,
+        Expr::Unary(expr) => &expr.attrs,
+        Expr::Unsafe(expr) => &expr.attrs,
+        Expr::While(expr) => &expr.attrs,
+        Expr::Yield(expr) => &expr.attrs,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> Cfg {
+        Cfg::Name(name.to_string())
+    }
+
+    fn key_value(key: &str, value: &str) -> Cfg {
+        Cfg::KeyValue(key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn nothing_is_known_without_a_cfg_set() {
+        let set = CfgSet::default();
+        assert_eq!(set.eval(&name("test")), None);
+        assert_eq!(set.eval(&key_value("feature", "std")), None);
+    }
+
+    #[test]
+    fn names_not_given_are_false_except_default_ones() {
+        let mut set = CfgSet::default();
+        set.add_feature("std");
+        assert_eq!(set.eval(&name("test")), Some(false));
+        assert_eq!(set.eval(&name("debug_assertions")), Some(true));
+        assert_eq!(
+            set.eval(&Cfg::Not(Box::new(name("debug_assertions")))),
+            Some(false)
+        );
+        assert_eq!(set.eval(&name("unix")), None);
+        assert_eq!(set.eval(&key_value("panic", "abort")), None);
+        set.add_cfg("test").unwrap();
+        assert_eq!(set.eval(&name("test")), Some(true));
+    }
+
+    #[test]
+    fn target_arch_determines_pointer_width_and_wasm() {
+        let mut set = CfgSet::default();
+        set.set_target_arch("wasm32");
+        assert_eq!(
+            set.eval(&key_value("target_pointer_width", "32")),
+            Some(true)
+        );
+        assert_eq!(
+            set.eval(&key_value("target_pointer_width", "64")),
+            Some(false)
+        );
+        assert_eq!(set.eval(&key_value("target_family", "wasm")), Some(true));
+        assert_eq!(set.eval(&key_value("target_family", "unix")), Some(false));
+
+        let mut set = CfgSet::default();
+        set.set_target_arch("x86_64");
+        assert_eq!(set.eval(&key_value("target_family", "unix")), None);
+    }
+
+    #[test]
+    fn target_family_given_as_a_cfg() {
+        let mut set = CfgSet::default();
+        set.add_cfg("unix").unwrap();
+        assert_eq!(set.eval(&name("unix")), Some(true));
+        assert_eq!(set.eval(&name("windows")), Some(false));
+        assert_eq!(set.eval(&key_value("target_family", "unix")), Some(true));
+        assert_eq!(set.eval(&key_value("target_os", "linux")), None);
+
+        let mut set = CfgSet::default();
+        set.add_cfg("target_os=\"windows\"").unwrap();
+        assert_eq!(set.eval(&name("windows")), Some(true));
+        assert_eq!(set.eval(&name("unix")), Some(false));
+        assert_eq!(set.eval(&key_value("target_os", "windows")), Some(true));
+    }
+
+    #[test]
+    fn target_os_determines_family() {
+        let mut set = CfgSet::default();
+        set.set_target_os("linux");
+        assert_eq!(set.eval(&name("unix")), Some(true));
+        assert_eq!(set.eval(&name("windows")), Some(false));
+        assert_eq!(
+            set.eval(&Cfg::All(vec![
+                name("unix"),
+                key_value("target_os", "linux")
+            ])),
+            Some(true)
+        );
+        assert_eq!(
+            set.eval(&Cfg::Any(vec![
+                name("windows"),
+                key_value("target_arch", "x86")
+            ])),
+            None
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::Path;
+
+use crate::toml;
+
+// Used when no `--config` is given, if present in the working directory.
+const DEFAULT_PATH: &str = "rustalyzer.toml";
+
+pub fn load(path: Option<&str>) -> Result<Option<toml::Table>, String> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(DEFAULT_PATH).is_file() => DEFAULT_PATH,
+        None => return Ok(None),
+    };
+    let src = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let config = toml::parse(&src).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(Some(config))
+}
+
+// Reads `table.key` as a list of strings, which may be omitted.
+pub fn strings(table: &toml::Table, section: &str, key: &str) -> Result<Vec<String>, String> {
+    let value = match table.get(key) {
+        Some(value) => value,
+        None => return Ok(Vec::new()),
+    };
+    let invalid = || format!("`{}.{}` must be an array of strings", section, key);
+    value
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|value| value.as_str().map(str::to_string).ok_or_else(invalid))
+        .collect()
+}
@@ -0,0 +1,302 @@
+// The packages locked in a `Cargo.lock` and where their sources can be found
+// without network access: path dependencies, a `vendor/` directory next to
+// the lockfile, and the registry sources and git checkouts that Cargo keeps
+// under `$CARGO_HOME`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::manifest;
+use crate::package::Package;
+use crate::toml;
+
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Path,
+    Vendor,
+    Registry,
+    Git,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let origin = match self {
+            Origin::Path => "path",
+            Origin::Vendor => "vendored",
+            Origin::Registry => "registry",
+            Origin::Git => "git",
+        };
+        f.write_str(origin)
+    }
+}
+
+pub struct Locked {
+    pub name: String,
+    pub version: String,
+    pub origin: Origin,
+    // The package directory, if its sources are present locally.
+    pub dir: Option<PathBuf>,
+    // Indices of the locked packages this one depends on.
+    pub dependencies: Vec<usize>,
+}
+
+impl Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+// The `Cargo.lock` of a manifest, which is next to the root of its
+// workspace, or next to the manifest itself outside of a workspace.
+pub fn find_lockfile(manifest: &Path) -> Option<PathBuf> {
+    let manifest = fs::canonicalize(manifest).ok()?;
+    let root = manifest::find_workspace(&manifest).map_or(manifest, |(root, _)| root);
+    let lockfile = root.parent()?.join("Cargo.lock");
+    lockfile.is_file().then_some(lockfile)
+}
+
+// Reads a lockfile and locates the sources of every package in it. `roots`
+// are the packages being analysed, which the path dependencies are
+// followed from.
+pub fn load(lockfile: &Path, roots: &[Package]) -> Result<Vec<Locked>, String> {
+    let error = |message: String| format!("{}: {}", lockfile.display(), message);
+    let src = fs::read_to_string(lockfile).map_err(|err| error(err.to_string()))?;
+    let lock = toml::parse(&src).map_err(|err| error(err.to_string()))?;
+    let lock_dir = lockfile.parent().unwrap_or(Path::new(""));
+    let entries = lock
+        .get("package")
+        .and_then(toml::Value::as_array)
+        .unwrap_or_default();
+
+    let mut paths = HashMap::new();
+    for root in roots {
+        path_dependencies(&root.name, &root.dir, &mut paths);
+    }
+    let cargo_home = cargo_home();
+
+    let mut packages = Vec::new();
+    let mut references = Vec::new();
+    let mut sources = Vec::new();
+    for entry in entries {
+        let field = |key: &str| entry.get(key).and_then(toml::Value::as_str);
+        let (name, version) = match (field("name"), field("version")) {
+            (Some(name), Some(version)) => (name.to_string(), version.to_string()),
+            _ => return Err(error("`[[package]]` without a name or version".to_string())),
+        };
+        let source = field("source").map(str::to_string);
+        let (origin, dir) = match &source {
+            None => (Origin::Path, paths.get(&name).cloned()),
+            Some(source) => match vendored(lock_dir, &name, &version) {
+                Some(dir) => (Origin::Vendor, Some(dir)),
+                None if source.starts_with("git+") => {
+                    let rev = source.rsplit_once('#').map_or("", |(_, rev)| rev);
+                    let dir = cargo_home
+                        .as_ref()
+                        .and_then(|home| git_checkout(home, rev, &name));
+                    (Origin::Git, dir)
+                }
+                None => {
+                    let dir = cargo_home
+                        .as_ref()
+                        .and_then(|home| registry(home, &name, &version));
+                    (Origin::Registry, dir)
+                }
+            },
+        };
+        let dependencies = entry
+            .get("dependencies")
+            .and_then(toml::Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(toml::Value::as_str)
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        references.push(dependencies);
+        sources.push(source);
+        packages.push(Locked {
+            name,
+            version,
+            origin,
+            dir,
+            dependencies: Vec::new(),
+        });
+    }
+
+    // Dependencies are written as `name`, `name version` or
+    // `name version (source)`, with only as much as needed to be unique.
+    for (i, dependencies) in references.into_iter().enumerate() {
+        for dependency in dependencies {
+            let mut parts = dependency.splitn(3, ' ');
+            let name = parts.next().unwrap_or_default();
+            let version = parts.next();
+            let source = parts
+                .next()
+                .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+            let found = packages.iter().enumerate().position(|(j, package)| {
+                package.name == name
+                    && version.is_none_or(|version| package.version == version)
+                    && source.is_none_or(|source| sources[j].as_deref() == Some(source))
+            });
+            match found {
+                Some(j) => packages[i].dependencies.push(j),
+                None => {
+                    return Err(error(format!("unknown dependency `{}`", dependency)));
+                }
+            }
+        }
+    }
+    Ok(packages)
+}
+
+// Collects the directories of the packages reachable through `path`
+// dependencies, by package name.
+fn path_dependencies(name: &str, dir: &Path, paths: &mut HashMap<String, PathBuf>) {
+    if paths.contains_key(name) {
+        return;
+    }
+    paths.insert(name.to_string(), dir.to_path_buf());
+    let manifest_path = dir.join("Cargo.toml");
+    let manifest = match manifest::read(&manifest_path) {
+        Some(manifest) => manifest,
+        None => return,
+    };
+    let workspace = manifest::find_workspace(&fs::canonicalize(&manifest_path).unwrap_or_default());
+
+    let mut tables: Vec<&toml::Value> = DEPENDENCY_TABLES
+        .iter()
+        .filter_map(|key| manifest.get(*key))
+        .collect();
+    let targets = manifest.get("target").and_then(toml::Value::as_table);
+    for target in targets.into_iter().flat_map(|targets| targets.values()) {
+        tables.extend(DEPENDENCY_TABLES.iter().filter_map(|key| target.get(key)));
+    }
+
+    for table in tables.iter().filter_map(|table| table.as_table()) {
+        for (key, dependency) in table {
+            // Inherited dependencies are resolved against the workspace.
+            let inherited =
+                dependency.get("workspace").and_then(toml::Value::as_bool) == Some(true);
+            let (dependency, base) = match &workspace {
+                Some((path, workspace)) if inherited => {
+                    let inherited = workspace
+                        .get("workspace")
+                        .and_then(|workspace| workspace.get("dependencies"))
+                        .and_then(|dependencies| dependencies.get(key));
+                    match inherited {
+                        Some(dependency) => (dependency, path.parent().unwrap_or(Path::new(""))),
+                        None => continue,
+                    }
+                }
+                _ => (dependency, dir),
+            };
+            let path = match dependency.get("path").and_then(toml::Value::as_str) {
+                Some(path) => base.join(path),
+                None => continue,
+            };
+            // The key is only the package name unless `package` renames it.
+            let name = manifest::read(&path.join("Cargo.toml"))
+                .and_then(|manifest| {
+                    let name = manifest.get("package")?.get("name")?.as_str()?;
+                    Some(name.to_string())
+                })
+                .unwrap_or_else(|| key.clone());
+            path_dependencies(&name, &path, paths);
+        }
+    }
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    match env::var_os("CARGO_HOME") {
+        Some(home) => Some(PathBuf::from(home)),
+        None => Some(PathBuf::from(env::var_os("HOME")?).join(".cargo")),
+    }
+}
+
+// `cargo vendor` names directories after the package, adding the version
+// when several versions of it are vendored.
+fn vendored(lock_dir: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    let vendor = lock_dir.join("vendor");
+    [
+        vendor.join(format!("{}-{}", name, version)),
+        vendor.join(name),
+    ]
+    .into_iter()
+    .find(|dir| {
+        let manifest_path = dir.join("Cargo.toml");
+        let manifest = match manifest::read(&manifest_path) {
+            Some(manifest) => manifest,
+            None => return false,
+        };
+        let found = manifest::package_field(&manifest_path, &manifest, "version");
+        found.as_ref().and_then(toml::Value::as_str) == Some(version)
+    })
+}
+
+// Registry sources are unpacked to `registry/src/<index>/<name>-<version>`,
+// with a directory for each registry index.
+fn registry(cargo_home: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    let mut indices: Vec<PathBuf> = fs::read_dir(cargo_home.join("registry/src"))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect();
+    indices.sort();
+    indices
+        .into_iter()
+        .map(|index| index.join(format!("{}-{}", name, version)))
+        .find(|dir| dir.join("Cargo.toml").is_file())
+}
+
+// Git dependencies are checked out to `git/checkouts/<repo>-<hash>/<rev>`,
+// where `rev` is an abbreviation of the locked commit, and the package may
+// be anywhere within the repository.
+fn git_checkout(cargo_home: &Path, rev: &str, name: &str) -> Option<PathBuf> {
+    let mut repos: Vec<PathBuf> = fs::read_dir(cargo_home.join("git/checkouts"))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .collect();
+    repos.sort();
+    for repo in repos {
+        let checkouts = match fs::read_dir(&repo) {
+            Ok(checkouts) => checkouts,
+            Err(_) => continue,
+        };
+        for checkout in checkouts.filter_map(Result::ok) {
+            let short = checkout.file_name().to_string_lossy().into_owned();
+            if short.is_empty() || !rev.starts_with(&short) {
+                continue;
+            }
+            if let Some(dir) = find_package(&checkout.path(), name) {
+                return Some(dir);
+            }
+        }
+    }
+    None
+}
+
+fn find_package(dir: &Path, name: &str) -> Option<PathBuf> {
+    let manifest = manifest::read(&dir.join("Cargo.toml"));
+    let found = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("package")?.get("name")?.as_str());
+    if found == Some(name) {
+        return Some(dir.to_path_buf());
+    }
+    let mut subdirs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter(|entry| !matches!(entry.file_name().to_str(), Some(".git" | "target")))
+        .map(|entry| entry.path())
+        .collect();
+    subdirs.sort();
+    subdirs
+        .into_iter()
+        .find_map(|subdir| find_package(&subdir, name))
+}
@@ -0,0 +1,488 @@
+// Renders messages about spans of a source file the way rustc does: a header,
+// the location, and the lines of source that the labels point at, followed by
+// notes and help. Columns are counted in characters, as proc_macro2 counts
+// them, and laid out by their display width with tabs expanded, so that
+// underlines stay under the code they point at.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt::{self, Display};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use colored::{Color, ColoredString, Colorize};
+use proc_macro2::LineColumn;
+
+// The width tabs are expanded to.
+const TAB_WIDTH: usize = 4;
+// Multi-line spans longer than this only show their first and last two lines.
+const MAX_SPAN_LINES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!(
+                "unknown color choice `{}`, expected `auto`, `always` or `never`",
+                s
+            )),
+        }
+    }
+}
+
+static COLOR: OnceLock<ColorChoice> = OnceLock::new();
+
+// Sets when output is coloured. Under `auto` it is whenever the stream it is
+// written to is a terminal and `NO_COLOR` is not set.
+pub fn set_color(choice: ColorChoice) {
+    let _ = COLOR.set(choice);
+    colored::control::set_override(is_colored(&io::stdout()));
+}
+
+fn is_colored(stream: &impl IsTerminal) -> bool {
+    match COLOR.get().copied().unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && stream.is_terminal()
+        }
+    }
+}
+
+// Writes a line to stderr, coloured according to whether stderr rather than
+// stdout is a terminal.
+pub fn eprint(message: impl Display) {
+    colored::control::set_override(is_colored(&io::stderr()));
+    let _ = writeln!(io::stderr(), "{}", message);
+    colored::control::set_override(is_colored(&io::stdout()));
+}
+
+#[derive(Clone, Copy)]
+pub enum Level {
+    Error,
+    Warning,
+    // How a region of unsafe code changed between two versions.
+    Added,
+    Removed,
+    Modified,
+}
+
+impl Level {
+    fn color(self) -> Color {
+        match self {
+            Level::Error | Level::Removed => Color::Red,
+            Level::Warning | Level::Modified => Color::Yellow,
+            Level::Added => Color::Green,
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Added => "added",
+            Level::Removed => "removed",
+            Level::Modified => "modified",
+        };
+        f.write_str(level)
+    }
+}
+
+// A span with a message. The primary labels are what a diagnostic is about,
+// and the secondary ones add context.
+pub struct Label<'a> {
+    pub start: LineColumn,
+    pub end: LineColumn,
+    pub message: &'a str,
+    pub primary: bool,
+}
+
+impl Label<'_> {
+    fn is_multiline(&self) -> bool {
+        self.end.line > self.start.line
+    }
+}
+
+pub struct Diagnostic<'a> {
+    pub level: Level,
+    pub header: &'a str,
+    pub filepath: &'a Path,
+    pub code: &'a str,
+    pub labels: Vec<Label<'a>>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    // Code in other places, or other files, that the labels refer to.
+    pub snippets: Vec<Diagnostic<'a>>,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new(level: Level, header: &'a str, filepath: &'a Path, code: &'a str) -> Self {
+        Diagnostic {
+            level,
+            header,
+            filepath,
+            code,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            snippets: Vec::new(),
+        }
+    }
+
+    pub fn primary(&mut self, start: LineColumn, end: LineColumn, message: &'a str) {
+        self.labels.push(Label {
+            start,
+            end,
+            message,
+            primary: true,
+        });
+    }
+
+    pub fn secondary(&mut self, start: LineColumn, end: LineColumn, message: &'a str) {
+        self.labels.push(Label {
+            start,
+            end,
+            message,
+            primary: false,
+        });
+    }
+
+    fn paint(&self, text: &str, label: &Label) -> ColoredString {
+        if label.primary {
+            text.color(self.level.color()).bold()
+        } else {
+            text.blue().bold()
+        }
+    }
+
+    // The lines to show: those the labels start and end on, and the lines
+    // in between unless the span is long.
+    fn lines(&self) -> BTreeSet<usize> {
+        let count = self.code.lines().count();
+        let mut lines = BTreeSet::new();
+        for label in &self.labels {
+            let (start, end) = (label.start.line, label.end.line.max(label.start.line));
+            if end - start < MAX_SPAN_LINES {
+                lines.extend(start..=end);
+            } else {
+                lines.extend([start, start + 1, end - 1, end]);
+            }
+        }
+        lines.retain(|&line| line >= 1 && line <= count);
+        lines
+    }
+
+    // Writes the underlines of the labels that start and end on `line`,
+    // primary ones over secondary ones. The message of the last label
+    // follows its underline unless it overlaps another label, and the others
+    // hang beneath from where their labels start.
+    fn write_underlines(
+        &self,
+        f: &mut fmt::Formatter,
+        gutter: &str,
+        margin: &dyn Fn(usize) -> String,
+        line: usize,
+        chars: &[char],
+    ) -> fmt::Result {
+        let mut labels: Vec<&Label> = self
+            .labels
+            .iter()
+            .filter(|label| !label.is_multiline() && label.start.line == line)
+            .collect();
+        if labels.is_empty() {
+            return Ok(());
+        }
+        labels.sort_by_key(|label| label.start.column);
+        // The display column each label starts at, and its width.
+        let spans: Vec<(usize, usize)> = labels
+            .iter()
+            .map(|label| {
+                let start = label.start.column.min(chars.len());
+                let end = label.end.column.clamp(start, chars.len());
+                (width(&chars[..start]), width(&chars[start..end]).max(1))
+            })
+            .collect();
+
+        let mut cells: Vec<Option<usize>> = Vec::new();
+        for primary in [false, true] {
+            for (i, &(start, len)) in spans.iter().enumerate() {
+                if labels[i].primary == primary {
+                    if cells.len() < start + len {
+                        cells.resize(start + len, None);
+                    }
+                    cells[start..start + len].fill(Some(i));
+                }
+            }
+        }
+        write!(f, "{} {}", gutter, margin(line))?;
+        let mut column = 0;
+        while column < cells.len() {
+            let run = cells[column..]
+                .iter()
+                .take_while(|&&cell| cell == cells[column])
+                .count();
+            match cells[column] {
+                Some(i) => {
+                    let underline = if labels[i].primary { "^" } else { "-" };
+                    write!(f, "{}", self.paint(&underline.repeat(run), labels[i]))?;
+                }
+                None => write!(f, "{}", " ".repeat(run))?,
+            }
+            column += run;
+        }
+
+        let last = labels.len() - 1;
+        let inline = spans[..last]
+            .iter()
+            .all(|&(start, len)| start + len <= spans[last].0);
+        if inline && !labels[last].message.is_empty() {
+            write!(f, " {}", self.paint(labels[last].message, labels[last]))?;
+        }
+        writeln!(f)?;
+
+        let hanging: Vec<usize> = (0..labels.len())
+            .filter(|&i| !(labels[i].message.is_empty() || inline && i == last))
+            .collect();
+        if hanging.is_empty() {
+            return Ok(());
+        }
+        // Connects the messages of the labels before `upto` to their labels.
+        let connectors = |f: &mut fmt::Formatter, upto: usize| -> Result<usize, fmt::Error> {
+            let mut at = 0;
+            for &i in hanging.iter().filter(|&&i| i < upto) {
+                let column = spans[i].0;
+                if column >= at {
+                    write!(f, "{}", " ".repeat(column - at))?;
+                    write!(f, "{}", self.paint("|", labels[i]))?;
+                    at = column + 1;
+                }
+            }
+            Ok(at)
+        };
+        write!(f, "{} {}", gutter, margin(line))?;
+        connectors(f, labels.len())?;
+        writeln!(f)?;
+        for &i in hanging.iter().rev() {
+            write!(f, "{} {}", gutter, margin(line))?;
+            let at = connectors(f, i)?;
+            write!(f, "{}", " ".repeat(spans[i].0.saturating_sub(at)))?;
+            writeln!(f, "{}", self.paint(labels[i].message, labels[i]))?;
+        }
+        Ok(())
+    }
+
+    // The width of the line numbers of the snippet and those of the
+    // others it is shown with.
+    fn digits(&self) -> usize {
+        let last = self.lines().last().copied().unwrap_or(1);
+        let own = last.to_string().len();
+        self.snippets
+            .iter()
+            .map(Diagnostic::digits)
+            .fold(own, usize::max)
+    }
+
+    // Writes the code the labels are in, introduced by `arrow` and the
+    // location of the main label.
+    fn write_snippet(&self, f: &mut fmt::Formatter, arrow: &str, digits: usize) -> fmt::Result {
+        let filename = self
+            .filepath
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or("main.rs".into());
+        let lines = self.lines();
+        let indent = " ".repeat(digits);
+        let pipe = "|".blue().bold();
+        let main = self
+            .labels
+            .iter()
+            .find(|label| label.primary)
+            .or(self.labels.first());
+        if let Some(main) = main {
+            writeln!(
+                f,
+                "{}{} {}:{}:{}",
+                indent,
+                arrow.blue().bold(),
+                filename,
+                main.start.line,
+                main.start.column + 1
+            )?;
+        }
+
+        // Multi-line spans are drawn in a margin left of the code, so that
+        // the code is shifted right when there are any.
+        let multiline: Vec<&Label> = self
+            .labels
+            .iter()
+            .filter(|label| label.is_multiline())
+            .collect();
+        let source: Vec<&str> = self.code.lines().collect();
+        // Spans starting at the code of their line are drawn from there,
+        // rather than from an underscore rule under where they start.
+        let from_margin = |label: &Label| {
+            let code = source.get(label.start.line - 1).copied().unwrap_or("");
+            code.chars()
+                .take(label.start.column)
+                .all(char::is_whitespace)
+        };
+        // Spans start with `/` on the code of their first line, and are
+        // continued with `|` on the lines and underlines below it.
+        let margin = |line: usize, code: bool| -> String {
+            if multiline.is_empty() {
+                return String::new();
+            }
+            for label in &multiline {
+                if label.start.line == line && from_margin(label) {
+                    let edge = if code { "/" } else { "|" };
+                    return format!("{} ", self.paint(edge, label));
+                }
+                if label.start.line < line && line <= label.end.line {
+                    return format!("{} ", self.paint("|", label));
+                }
+            }
+            "  ".to_string()
+        };
+        let underline_margin = |line| margin(line, false);
+        let gutter = format!("{} {}", indent, pipe);
+
+        if !lines.is_empty() {
+            writeln!(f, "{}", gutter)?;
+        }
+        let mut previous = None;
+        for &line in &lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                writeln!(f, "{}", "...".blue().bold())?;
+            }
+            previous = Some(line);
+
+            let chars: Vec<char> = source[line - 1].trim_end().chars().collect();
+            let number = format!("{:>digits$}", line, digits = digits);
+            writeln!(
+                f,
+                "{} {} {}{}",
+                number.blue().bold(),
+                pipe,
+                margin(line, true),
+                expand_tabs(&chars)
+            )?;
+
+            for label in &multiline {
+                if label.start.line == line && !from_margin(label) {
+                    let column = width(&chars[..label.start.column.min(chars.len())]);
+                    let rule = format!(" {}^", "_".repeat(column + 1));
+                    writeln!(f, "{} {}", gutter, self.paint(&rule, label))?;
+                }
+            }
+            self.write_underlines(f, &gutter, &underline_margin, line, &chars)?;
+            for label in &multiline {
+                if label.end.line == line {
+                    let end = label.end.column.clamp(1, chars.len().max(1));
+                    let column = width(&chars[..end - 1]);
+                    let rule = format!("|{}^", "_".repeat(column + 1));
+                    write!(f, "{} {}", gutter, self.paint(&rule, label))?;
+                    if !label.message.is_empty() {
+                        write!(f, " {}", self.paint(label.message, label))?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let color = self.level.color();
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{}{}",
+            self.level.to_string().color(color).bold(),
+            format!(": {}", self.header).bold()
+        )?;
+
+        let digits = self.digits();
+        let indent = " ".repeat(digits);
+        self.write_snippet(f, "-->", digits)?;
+        for snippet in &self.snippets {
+            writeln!(f, "{} {}", indent, "|".blue().bold())?;
+            snippet.write_snippet(f, ":::", digits)?;
+        }
+        for (kind, messages) in [("note", &self.notes), ("help", &self.help)] {
+            for message in messages {
+                writeln!(
+                    f,
+                    "{} {} {}: {}",
+                    indent,
+                    "=".blue().bold(),
+                    kind.bold(),
+                    message
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn char_width(c: char) -> usize {
+    match c {
+        '\t' => TAB_WIDTH,
+        // Combining marks, zero width spaces and joiners, and variation
+        // selectors.
+        '\u{0300}'..='\u{036f}'
+        | '\u{200b}'..='\u{200f}'
+        | '\u{fe00}'..='\u{fe0f}'
+        | '\u{fe20}'..='\u{fe2f}' => 0,
+        c if c.is_control() => 0,
+        // East Asian wide and fullwidth characters, and emoji.
+        '\u{1100}'..='\u{115f}'
+        | '\u{2e80}'..='\u{303e}'
+        | '\u{3041}'..='\u{33ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{a000}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{1f300}'..='\u{1f64f}'
+        | '\u{1f900}'..='\u{1f9ff}'
+        | '\u{20000}'..='\u{2fffd}'
+        | '\u{30000}'..='\u{3fffd}' => 2,
+        _ => 1,
+    }
+}
+
+// The number of columns `chars` take up on a terminal.
+fn width(chars: &[char]) -> usize {
+    chars.iter().copied().map(char_width).sum()
+}
+
+fn expand_tabs(chars: &[char]) -> String {
+    let mut line = String::new();
+    for &c in chars {
+        match c {
+            '\t' => line.push_str(&" ".repeat(TAB_WIDTH)),
+            _ => line.push(c),
+        }
+    }
+    line
+}
@@ -0,0 +1,294 @@
+// Compares the unsafe code of two versions of a crate. The unsafe blocks,
+// fns, impls and traits of each version are keyed by the path of the item
+// they belong to, since their files and line numbers may well have changed.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use proc_macro2::LineColumn;
+use quote::ToTokens;
+use syn::visit::{self, Visit};
+use syn::{
+    ExprUnsafe, ImplItem, ImplItemFn, Item, ItemConst, ItemFn, ItemImpl, ItemMod, ItemStatic,
+    ItemTrait, Macro, TraitItem, TraitItemFn,
+};
+
+use crate::cfg::{self, CfgSet};
+use crate::macros::{MacroBody, Macros};
+use crate::pretty;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RegionKind {
+    Block,
+    Fn,
+    Impl,
+    Trait,
+}
+
+impl Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            RegionKind::Block => "unsafe block",
+            RegionKind::Fn => "unsafe fn",
+            RegionKind::Impl => "unsafe impl",
+            RegionKind::Trait => "unsafe trait",
+        };
+        f.write_str(kind)
+    }
+}
+
+pub struct Region {
+    pub kind: RegionKind,
+    // The path of the item the region is in, or is, e.g. `demo::util::read`.
+    pub owner: String,
+    // The file the region is in, as an index into the caller's files.
+    pub file: usize,
+    pub start: LineColumn,
+    pub end: LineColumn,
+    // The tokens of the region, so that changes in formatting and comments
+    // are not mistaken for changes to the code.
+    tokens: String,
+}
+
+// Collects the unsafe regions of a file whose module path is `module`,
+// skipping items that `cfg` rules out. The regions in the input of macros
+// that `macros` can parse are collected too.
+pub fn regions(
+    file: usize,
+    ast: &syn::File,
+    module: &[String],
+    cfg: &CfgSet,
+    macros: &Macros,
+) -> Vec<Region> {
+    let mut collector = Collector {
+        file,
+        cfg,
+        macros,
+        path: module.to_vec(),
+        regions: Vec::new(),
+    };
+    collector.visit_file(ast);
+    collector.regions
+}
+
+struct Collector<'a> {
+    file: usize,
+    cfg: &'a CfgSet,
+    macros: &'a Macros,
+    path: Vec<String>,
+    regions: Vec<Region>,
+}
+
+impl Collector<'_> {
+    fn push(&mut self, kind: RegionKind, start: LineColumn, end: LineColumn, tokens: String) {
+        self.regions.push(Region {
+            kind,
+            owner: self.path.join("::"),
+            file: self.file,
+            start,
+            end,
+            tokens,
+        });
+    }
+
+    fn nested<F>(&mut self, name: String, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.path.push(name);
+        f(self);
+        self.path.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item(&mut self, node: &'ast Item) {
+        if self.cfg.is_active(cfg::item_attrs(node)) {
+            visit::visit_item(self, node);
+        }
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        if self.cfg.is_active(cfg::impl_item_attrs(node)) {
+            visit::visit_impl_item(self, node);
+        }
+    }
+
+    fn visit_trait_item(&mut self, node: &'ast TraitItem) {
+        if self.cfg.is_active(cfg::trait_item_attrs(node)) {
+            visit::visit_trait_item(self, node);
+        }
+    }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        self.nested(node.ident.to_string(), |v| visit::visit_item_mod(v, node));
+    }
+
+    fn visit_item_const(&mut self, node: &'ast ItemConst) {
+        self.nested(node.ident.to_string(), |v| visit::visit_item_const(v, node));
+    }
+
+    fn visit_item_static(&mut self, node: &'ast ItemStatic) {
+        self.nested(node.ident.to_string(), |v| {
+            visit::visit_item_static(v, node)
+        });
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.nested(node.sig.ident.to_string(), |v| {
+            if let Some(unsafety) = &node.sig.unsafety {
+                let end = node.block.brace_token.span.close().end();
+                let tokens = format!(
+                    "{} {}",
+                    pretty::tokens(&node.sig),
+                    pretty::tokens(&node.block)
+                );
+                v.push(RegionKind::Fn, unsafety.span.start(), end, tokens);
+            }
+            visit::visit_item_fn(v, node);
+        });
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.nested(node.sig.ident.to_string(), |v| {
+            if let Some(unsafety) = &node.sig.unsafety {
+                let end = node.block.brace_token.span.close().end();
+                let tokens = format!(
+                    "{} {}",
+                    pretty::tokens(&node.sig),
+                    pretty::tokens(&node.block)
+                );
+                v.push(RegionKind::Fn, unsafety.span.start(), end, tokens);
+            }
+            visit::visit_impl_item_fn(v, node);
+        });
+    }
+
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        self.nested(node.sig.ident.to_string(), |v| {
+            if let (Some(unsafety), Some(block)) = (&node.sig.unsafety, &node.default) {
+                let end = block.brace_token.span.close().end();
+                let tokens = format!("{} {}", pretty::tokens(&node.sig), pretty::tokens(block));
+                v.push(RegionKind::Fn, unsafety.span.start(), end, tokens);
+            }
+            visit::visit_trait_item_fn(v, node);
+        });
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        self.nested(pretty::impl_name(node), |v| {
+            if let Some(unsafety) = &node.unsafety {
+                let end = node.brace_token.span.close().end();
+                let generics = &node.generics;
+                let tokens = format!(
+                    "{} {}",
+                    pretty::tokens(generics),
+                    pretty::tokens(&generics.where_clause)
+                );
+                v.push(RegionKind::Impl, unsafety.span.start(), end, tokens);
+            }
+            for item in &node.items {
+                v.visit_impl_item(item);
+            }
+        });
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        self.nested(node.ident.to_string(), |v| {
+            if let Some(unsafety) = &node.unsafety {
+                let end = node.brace_token.span.close().end();
+                let generics = &node.generics;
+                let tokens = format!(
+                    "{}: {} {}",
+                    pretty::tokens(generics),
+                    pretty::tokens(&node.supertraits),
+                    pretty::tokens(&generics.where_clause)
+                );
+                v.push(RegionKind::Trait, unsafety.span.start(), end, tokens);
+            }
+            for item in &node.items {
+                v.visit_trait_item(item);
+            }
+        });
+    }
+
+    // Blocks nested in an unsafe block are part of it.
+    fn visit_expr_unsafe(&mut self, node: &'ast ExprUnsafe) {
+        let start = node.unsafe_token.span.start();
+        let end = node.block.brace_token.span.close().end();
+        let tokens = node.block.to_token_stream().to_string();
+        self.push(RegionKind::Block, start, end, tokens);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        match self.macros.parse(node) {
+            Some(MacroBody::Exprs(exprs)) => {
+                for expr in &exprs {
+                    self.visit_expr(expr);
+                }
+            }
+            Some(MacroBody::Stmts(stmts)) => {
+                for stmt in &stmts {
+                    self.visit_stmt(stmt);
+                }
+            }
+            Some(MacroBody::Items(items)) => {
+                for item in &items {
+                    self.visit_item(item);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+pub enum Change<'a> {
+    Added(&'a Region),
+    Removed(&'a Region),
+    // The old version of a region and the new one.
+    Modified(&'a Region, &'a Region),
+}
+
+// Matches the regions of the old version with those of the new one. Within
+// an item, identical regions are matched first and the others in order, so
+// that a block that was edited shows up as modified rather than as removed
+// and added. Returns the changes by item path, and how many regions are
+// unchanged.
+pub fn compare<'a>(old: &'a [Region], new: &'a [Region]) -> (Vec<Change<'a>>, usize) {
+    // The old and new regions of each kind within each item.
+    let mut groups: BTreeMap<_, (Vec<&Region>, Vec<&Region>)> = BTreeMap::new();
+    for region in old {
+        let key = (region.owner.as_str(), region.kind);
+        groups.entry(key).or_default().0.push(region);
+    }
+    for region in new {
+        let key = (region.owner.as_str(), region.kind);
+        groups.entry(key).or_default().1.push(region);
+    }
+
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    for (_, (mut old, mut new)) in groups {
+        old.retain(
+            |region| match new.iter().position(|n| n.tokens == region.tokens) {
+                Some(i) => {
+                    new.remove(i);
+                    unchanged += 1;
+                    false
+                }
+                None => true,
+            },
+        );
+        let mut old = old.into_iter();
+        let mut new = new.into_iter();
+        loop {
+            match (old.next(), new.next()) {
+                (Some(old), Some(new)) => changes.push(Change::Modified(old, new)),
+                (Some(old), None) => changes.push(Change::Removed(old)),
+                (None, Some(new)) => changes.push(Change::Added(new)),
+                (None, None) => break,
+            }
+        }
+    }
+    (changes, unchanged)
+}
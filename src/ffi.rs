@@ -0,0 +1,191 @@
+use std::fmt::{self, Display};
+
+use proc_macro2::LineColumn;
+use syn::spanned::Spanned;
+use syn::{
+    Abi, Attribute, Expr, ExprLit, ForeignItem, Ident, ItemForeignMod, ItemStatic, Lit, Meta,
+    Signature, StaticMutability, Type,
+};
+
+use crate::pretty;
+
+pub enum FfiKind {
+    // An `extern "ABI" { ... }` block, optionally the `#[link]`ed library.
+    Block {
+        unsafety: bool,
+        link: Option<String>,
+    },
+    // A function or static declared inside an extern block.
+    Import,
+    // A `#[no_mangle]` or `#[export_name]` function or static we define.
+    Export,
+}
+
+pub struct FfiItem {
+    pub kind: FfiKind,
+    pub symbol: String,
+    pub abi: String,
+    pub signature: String,
+    pub start: LineColumn,
+}
+
+impl FfiItem {
+    pub fn is_import(&self) -> bool {
+        matches!(self.kind, FfiKind::Import)
+    }
+
+    pub fn is_export(&self) -> bool {
+        matches!(self.kind, FfiKind::Export)
+    }
+}
+
+impl Display for FfiItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            FfiKind::Block { unsafety, link } => {
+                let unsafety = if *unsafety { "unsafe " } else { "" };
+                write!(f, "{}extern \"{}\" block", unsafety, self.abi)?;
+                if let Some(link) = link {
+                    write!(f, " linking `{}`", link)?;
+                }
+                Ok(())
+            }
+            FfiKind::Import => write!(
+                f,
+                "import `{}` ({}): {}",
+                self.symbol, self.abi, self.signature
+            ),
+            FfiKind::Export => write!(
+                f,
+                "export `{}` ({}): {}",
+                self.symbol, self.abi, self.signature
+            ),
+        }
+    }
+}
+
+// Returns the extern block itself followed by each fn and static it declares.
+pub fn foreign_mod(node: &ItemForeignMod) -> Vec<FfiItem> {
+    let abi = abi_name(&node.abi);
+    let link = node
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("link"))
+        .find_map(|attr| {
+            let mut name = None;
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+                } else if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                }
+                Ok(())
+            });
+            name
+        });
+
+    let mut items = vec![FfiItem {
+        kind: FfiKind::Block {
+            unsafety: node.unsafety.is_some(),
+            link,
+        },
+        symbol: String::new(),
+        abi: abi.clone(),
+        signature: String::new(),
+        start: match &node.unsafety {
+            Some(unsafety) => unsafety.span.start(),
+            None => node.abi.extern_token.span.start(),
+        },
+    }];
+
+    for item in &node.items {
+        let (attrs, ident, signature, start) = match item {
+            ForeignItem::Fn(item) => (
+                &item.attrs,
+                &item.sig.ident,
+                pretty::tokens(&item.sig),
+                item.sig.span().start(),
+            ),
+            ForeignItem::Static(item) => (
+                &item.attrs,
+                &item.ident,
+                static_signature(&item.mutability, &item.ident, &item.ty),
+                item.static_token.span.start(),
+            ),
+            _ => continue,
+        };
+        let symbol = attr_str(attrs, "link_name").unwrap_or_else(|| ident.to_string());
+        items.push(FfiItem {
+            kind: FfiKind::Import,
+            symbol,
+            abi: abi.clone(),
+            signature,
+            start,
+        });
+    }
+
+    items
+}
+
+// A function we define that is reachable from foreign code by name.
+pub fn exported_fn(attrs: &[Attribute], sig: &Signature) -> Option<FfiItem> {
+    let abi = sig.abi.as_ref()?;
+    let symbol = export_symbol(attrs)?.unwrap_or_else(|| sig.ident.to_string());
+    Some(FfiItem {
+        kind: FfiKind::Export,
+        symbol,
+        abi: abi_name(abi),
+        signature: pretty::tokens(sig),
+        start: sig.span().start(),
+    })
+}
+
+pub fn exported_static(node: &ItemStatic) -> Option<FfiItem> {
+    let symbol = export_symbol(&node.attrs)?.unwrap_or_else(|| node.ident.to_string());
+    Some(FfiItem {
+        kind: FfiKind::Export,
+        symbol,
+        abi: "C".to_string(),
+        signature: static_signature(&node.mutability, &node.ident, &node.ty),
+        start: node.static_token.span.start(),
+    })
+}
+
+fn static_signature(mutability: &StaticMutability, ident: &Ident, ty: &Type) -> String {
+    let mutability = match mutability {
+        StaticMutability::Mut(_) => "mut ",
+        _ => "",
+    };
+    format!("static {}{}: {}", mutability, ident, pretty::tokens(ty))
+}
+
+fn abi_name(abi: &Abi) -> String {
+    match &abi.name {
+        Some(name) => name.value(),
+        None => "C".to_string(),
+    }
+}
+
+// `Some(Some(name))` for `#[export_name = "name"]`, `Some(None)` for
+// `#[no_mangle]` and `None` if the item is not exported.
+fn export_symbol(attrs: &[Attribute]) -> Option<Option<String>> {
+    if let Some(name) = attr_str(attrs, "export_name") {
+        return Some(Some(name));
+    }
+    if attrs.iter().any(|attr| attr.path().is_ident("no_mangle")) {
+        return Some(None);
+    }
+    None
+}
+
+fn attr_str(attrs: &[Attribute], name: &str) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident(name) => match &meta.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => Some(s.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
@@ -0,0 +1,107 @@
+// Reads the files being analysed, from disk, from the archives given as
+// inputs or from buffers such as the source read from stdin. A file within an
+// archive is addressed by the path of the archive followed by its path in the
+// archive, as in `foo-1.0.0.crate/foo-1.0.0/src/lib.rs`, so that it can be
+// reported like any other file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::archive::Archive;
+
+#[derive(Default)]
+pub struct Files {
+    archives: Vec<(PathBuf, Archive)>,
+    buffers: HashMap<PathBuf, String>,
+}
+
+impl Files {
+    pub fn add_archive(&mut self, path: PathBuf, archive: Archive) {
+        self.archives.push((path, archive));
+    }
+
+    // Makes `path` read as `src`, whether or not there is a file there.
+    pub fn add_buffer(&mut self, path: PathBuf, src: String) {
+        self.buffers.insert(path, src);
+    }
+
+    // The archive a path is in, and the path within it. There is no file
+    // system to resolve `..` against, as in `#[path = "../x.rs"]`, so it is
+    // resolved lexically.
+    fn archived(&self, path: &Path) -> Option<(&Path, &Archive, PathBuf)> {
+        self.archives.iter().find_map(|(root, archive)| {
+            let mut inner = PathBuf::new();
+            for component in path.strip_prefix(root).ok()?.components() {
+                match component {
+                    Component::ParentDir => {
+                        inner.pop();
+                    }
+                    Component::Normal(part) => inner.push(part),
+                    _ => {}
+                }
+            }
+            Some((root.as_path(), archive, inner))
+        })
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        if let Some(src) = self.buffers.get(path) {
+            return Ok(src.clone().into_bytes());
+        }
+        let (_, archive, inner) = match self.archived(path) {
+            Some(archived) => archived,
+            None => return fs::read(path),
+        };
+        archive
+            .file(&inner)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        if self.buffers.contains_key(path) {
+            return true;
+        }
+        match self.archived(path) {
+            Some((_, archive, inner)) => archive.file(&inner).is_some(),
+            None => path.is_file(),
+        }
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        match self.archived(path) {
+            Some((_, archive, inner)) => archive.is_dir(&inner),
+            None => path.is_dir(),
+        }
+    }
+
+    // The paths of the entries of a directory, in no particular order.
+    pub fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.archived(path) {
+            Some((root, archive, inner)) if archive.is_dir(&inner) => Ok(archive
+                .entries(&inner)
+                .into_iter()
+                .map(|entry| root.join(entry))
+                .collect()),
+            Some(_) => Err(io::Error::from(io::ErrorKind::NotFound)),
+            None => fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect(),
+        }
+    }
+
+    // The canonical form of a path on disk, which archived paths already are.
+    pub fn canonicalize(&self, path: &Path) -> PathBuf {
+        match self.archived(path) {
+            Some(_) => path.to_path_buf(),
+            None => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Glob patterns with gitignore semantics, used for ignore files as well as
+// `--exclude` and `--include`. A pattern without a slash matches a file or
+// directory name at any depth, a pattern with one is relative to the
+// directory the pattern applies to. `*` and `?` do not match `/`, `**`
+// matches any number of directories, and a trailing `/` only matches
+// directories.
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    // `?`
+    Any,
+    // `*`
+    Star,
+    // `**/`: nothing, or any number of whole directories.
+    Dirs,
+    // A trailing `/**`: everything below the directory.
+    Rest,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    dir_only: bool,
+    // `!pattern` in an ignore file, re-including what it matches.
+    pub negated: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let invalid = |reason: &str| format!("invalid glob `{}`: {}", pattern, reason);
+        let mut rest = pattern;
+        let negated = rest.starts_with('!');
+        if negated {
+            rest = &rest[1..];
+        }
+        let dir_only = rest.ends_with('/') && !rest.ends_with("\\/");
+        if dir_only {
+            rest = &rest[..rest.len() - 1];
+        }
+        if rest.is_empty() {
+            return Err(invalid("empty pattern"));
+        }
+        // A slash anywhere but at the end anchors the pattern.
+        let anchored = rest.contains('/');
+        let rest = rest.strip_prefix('/').unwrap_or(rest);
+
+        let mut tokens = Vec::new();
+        if !anchored {
+            tokens.push(Token::Dirs);
+        }
+        let chars: Vec<char> = rest.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let at_segment_start = i == 0 || chars[i - 1] == '/';
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') && at_segment_start => {
+                    match chars.get(i + 2) {
+                        Some('/') => {
+                            tokens.push(Token::Dirs);
+                            i += 3;
+                        }
+                        None => {
+                            tokens.push(Token::Rest);
+                            i += 2;
+                        }
+                        // `**` within a name is the same as `*`.
+                        Some(_) => {
+                            tokens.push(Token::Star);
+                            i += 2;
+                        }
+                    }
+                    continue;
+                }
+                '*' => {
+                    tokens.push(Token::Star);
+                    while chars.get(i + 1) == Some(&'*') {
+                        i += 1;
+                    }
+                }
+                '?' => tokens.push(Token::Any),
+                '[' => {
+                    let (class, end) =
+                        parse_class(&chars, i).ok_or_else(|| invalid("unclosed `[`"))?;
+                    tokens.push(class);
+                    i = end;
+                }
+                '\\' => {
+                    i += 1;
+                    let c = *chars.get(i).ok_or_else(|| invalid("trailing `\\`"))?;
+                    tokens.push(Token::Char(c));
+                }
+                c => tokens.push(Token::Char(c)),
+            }
+            i += 1;
+        }
+
+        Ok(Glob {
+            tokens,
+            dir_only,
+            negated,
+        })
+    }
+
+    // Matches a `/` separated path relative to the pattern's directory.
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let chars: Vec<char> = path.chars().collect();
+        match_tokens(&self.tokens, &chars)
+    }
+}
+
+// Parses `[...]` starting at `start`, returning the class and the index of
+// the closing bracket.
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i));
+        }
+        first = false;
+        let c = if c == '\\' {
+            i += 1;
+            *chars.get(i)?
+        } else {
+            c
+        };
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return path.is_empty(),
+    };
+    match token {
+        Token::Char(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::Any => path.first().is_some_and(|&c| c != '/') && match_tokens(rest, &path[1..]),
+        Token::Class { negated, ranges } => match path.first() {
+            Some(&c) if c != '/' => {
+                let in_class = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                in_class != *negated && match_tokens(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            let segment = path.iter().take_while(|&&c| c != '/').count();
+            (0..=segment).any(|n| match_tokens(rest, &path[n..]))
+        }
+        Token::Dirs => {
+            if match_tokens(rest, path) {
+                return true;
+            }
+            path.iter()
+                .enumerate()
+                .filter(|&(_, &c)| c == '/')
+                .any(|(i, _)| match_tokens(rest, &path[i + 1..]))
+        }
+        Token::Rest => !path.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().matches(path, false)
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        assert!(matches("*.rs", "lib.rs"));
+        assert!(matches("*.rs", "src/sys/unix.rs"));
+        assert!(!matches("*.rs", "lib.rs.orig"));
+        assert!(matches("target", "sub/target"));
+        assert!(!matches("target", "sub/target2"));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_directory() {
+        assert!(matches("/target", "target"));
+        assert!(!matches("/target", "sub/target"));
+        assert!(matches("src/*.rs", "src/lib.rs"));
+        assert!(!matches("src/*.rs", "src/sys/unix.rs"));
+        assert!(!matches("src/*.rs", "crate/src/lib.rs"));
+    }
+
+    #[test]
+    fn double_stars() {
+        assert!(matches("**/gen", "gen"));
+        assert!(matches("**/gen", "a/b/gen"));
+        assert!(matches("src/**/mod.rs", "src/mod.rs"));
+        assert!(matches("src/**/mod.rs", "src/a/b/mod.rs"));
+        assert!(!matches("src/**/mod.rs", "tests/a/mod.rs"));
+        assert!(matches("vendor/**", "vendor/a/b.rs"));
+        assert!(!matches("vendor/**", "vendor"));
+        // Within a name, `**` is a single `*`.
+        assert!(matches("/a**.rs", "abc.rs"));
+        assert!(!matches("/a**.rs", "a/b.rs"));
+    }
+
+    #[test]
+    fn single_characters_and_classes() {
+        assert!(matches("?.rs", "a.rs"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(matches("[a-c]x.rs", "bx.rs"));
+        assert!(!matches("[a-c]x.rs", "dx.rs"));
+        assert!(matches("[!a-c]x.rs", "dx.rs"));
+        assert!(!matches("[^a-c]x.rs", "ax.rs"));
+        assert!(matches("[]-]", "]"));
+        assert!(matches("[]-]", "-"));
+        assert!(matches(r"\[x\]", "[x]"));
+        assert!(!matches("a[!x]b", "a/b"));
+    }
+
+    #[test]
+    fn directories_and_negation() {
+        let glob = Glob::new("!build/").unwrap();
+        assert!(glob.negated);
+        assert!(glob.matches("build", true));
+        assert!(!glob.matches("build", false));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!(
+            Glob::new("/").unwrap_err(),
+            "invalid glob `/`: empty pattern"
+        );
+        assert_eq!(
+            Glob::new("[ab").unwrap_err(),
+            "invalid glob `[ab`: unclosed `[`"
+        );
+        assert_eq!(
+            Glob::new(r"a\").unwrap_err(),
+            r"invalid glob `a\`: trailing `\`"
+        );
+    }
+}
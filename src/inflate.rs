@@ -0,0 +1,424 @@
+// A small gzip decompressor (RFC 1951 and RFC 1952), sufficient for the
+// `.crate` and `.tar.gz` archives that can be given as inputs. Huffman codes
+// are decoded a bit at a time, which is slow but simple.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// The order code lengths of the code length alphabet are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+// The most an archive may decompress to, so that a small but malicious input
+// cannot exhaust memory.
+const MAX_OUTPUT: usize = 512 << 20;
+
+// Decompresses a gzip stream, which may consist of several members.
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    gunzip_limited(data, MAX_OUTPUT)
+}
+
+fn gunzip_limited(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.is_empty() {
+        return Err("empty gzip stream".to_string());
+    }
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        pos = member(data, pos, &mut out, limit)?;
+    }
+    Ok(out)
+}
+
+fn member(data: &[u8], start: usize, out: &mut Vec<u8>, limit: usize) -> Result<usize, String> {
+    let header = data.get(start..start + 10).ok_or("truncated gzip header")?;
+    if header[..2] != [0x1f, 0x8b] {
+        return Err("not a gzip stream".to_string());
+    }
+    if header[2] != 8 {
+        return Err(format!("unsupported compression method {}", header[2]));
+    }
+    let flags = header[3];
+    let mut pos = start + 10;
+    // FEXTRA, FNAME, FCOMMENT and FHCRC, in the order they appear.
+    if flags & 4 != 0 {
+        let len = data.get(pos..pos + 2).ok_or("truncated gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [8, 16] {
+        if flags & flag != 0 {
+            let len = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or("truncated gzip header")?;
+            pos += len + 1;
+        }
+    }
+    if flags & 2 != 0 {
+        pos += 2;
+    }
+    if pos > data.len() {
+        return Err("truncated gzip header".to_string());
+    }
+
+    let begin = out.len();
+    let mut input = Bits {
+        data,
+        pos,
+        bit: 0,
+        bits: 0,
+    };
+    inflate(&mut input, out, limit)?;
+    let pos = input.pos;
+    let trailer = data.get(pos..pos + 8).ok_or("truncated gzip trailer")?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc32(&out[begin..]) != crc || (out.len() - begin) as u32 != size {
+        return Err("gzip checksum mismatch".to_string());
+    }
+    Ok(pos + 8)
+}
+
+// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+    bits: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.bits < count {
+            let byte = *self.data.get(self.pos).ok_or("truncated deflate stream")?;
+            self.pos += 1;
+            self.bit |= (byte as u32) << self.bits;
+            self.bits += 8;
+        }
+        let value = self.bit & ((1 << count) - 1);
+        self.bit >>= count;
+        self.bits -= count;
+        Ok(value)
+    }
+
+    // Drops the bits left in the current byte.
+    fn align(&mut self) {
+        self.bit = 0;
+        self.bits = 0;
+    }
+}
+
+// A canonical Huffman code, as the number of codes of each length and the
+// symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err("over-subscribed huffman code".to_string());
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= input.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+fn inflate(input: &mut Bits, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored(input, out, limit)?,
+            1 => {
+                let (lengths, distances) = fixed();
+                codes(input, out, limit, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic(input)?;
+                codes(input, out, limit, &lengths, &distances)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            input.align();
+            return Ok(());
+        }
+    }
+}
+
+fn too_large(limit: usize) -> String {
+    format!("decompressed size exceeds {} bytes", limit)
+}
+
+fn stored(input: &mut Bits, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    input.align();
+    let header = input
+        .data
+        .get(input.pos..input.pos + 4)
+        .ok_or("truncated stored block")?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err("corrupt stored block length".to_string());
+    }
+    let start = input.pos + 4;
+    let block = input
+        .data
+        .get(start..start + len as usize)
+        .ok_or("truncated stored block")?;
+    if out.len() + block.len() > limit {
+        return Err(too_large(limit));
+    }
+    out.extend_from_slice(block);
+    input.pos = start + len as usize;
+    Ok(())
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    // The fixed codes are complete, so building them cannot fail.
+    let lengths = Huffman::new(&lengths).unwrap();
+    let distances = Huffman::new(&[5; 30]).unwrap();
+    (lengths, distances)
+}
+
+fn dynamic(input: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let nlen = input.bits(5)? as usize + 257;
+    let ndist = input.bits(5)? as usize + 1;
+    let ncode = input.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("too many deflate codes".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[index] = input.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_lengths.decode(input)?;
+        let (length, repeat) = match symbol {
+            0..=15 => {
+                lengths[index] = symbol as u8;
+                index += 1;
+                continue;
+            }
+            16 => {
+                let previous = *index
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or("repeated code length without a previous one")?;
+                (previous, 3 + input.bits(2)?)
+            }
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        let end = index + repeat as usize;
+        if end > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[index..end].fill(length);
+        index = end;
+    }
+    if lengths[256] == 0 {
+        return Err("missing end of block code".to_string());
+    }
+    let distances = Huffman::new(&lengths[nlen..])?;
+    let lengths = Huffman::new(&lengths[..nlen])?;
+    Ok((lengths, distances))
+}
+
+fn codes(
+    input: &mut Bits,
+    out: &mut Vec<u8>,
+    limit: usize,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(input)? as usize;
+        if symbol < 256 {
+            if out.len() == limit {
+                return Err(too_large(limit));
+            }
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err("invalid length code".to_string());
+        }
+        let length =
+            LENGTH_BASE[symbol] as usize + input.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+        let symbol = distances.decode(input)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err("invalid distance code".to_string());
+        }
+        let distance =
+            DISTANCE_BASE[symbol] as usize + input.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+        if distance > out.len() {
+            return Err("distance too far back".to_string());
+        }
+        if out.len() + length > limit {
+            return Err(too_large(limit));
+        }
+        // The copy may overlap what it produces.
+        let start = out.len() - distance;
+        for i in 0..length {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "unsafe { *p }" in a stored block.
+    const STORED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x0d, 0x00, 0xf2, 0xff,
+        0x75, 0x6e, 0x73, 0x61, 0x66, 0x65, 0x20, 0x7b, 0x20, 0x2a, 0x70, 0x20, 0x7d, 0xfd, 0xde,
+        0x8c, 0xc8, 0x0d, 0x00, 0x00, 0x00,
+    ];
+    // "unsafe { *p } unsafe { *q }" with the fixed codes and a back reference.
+    const FIXED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x2b, 0xcd, 0x2b, 0x4e, 0x4c,
+        0x4b, 0x55, 0xa8, 0x56, 0xd0, 0x2a, 0x50, 0xa8, 0x55, 0x28, 0x85, 0xf3, 0x0a, 0x15, 0x6a,
+        0x01, 0x29, 0x2e, 0xa2, 0x29, 0x1b, 0x00, 0x00, 0x00,
+    ];
+    // "abracadabra" three times with dynamic codes.
+    const DYNAMIC: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x05, 0xc1, 0x31, 0x01, 0x00,
+        0x00, 0x0c, 0x02, 0xa0, 0xac, 0x38, 0x13, 0xd8, 0xff, 0x18, 0xc8, 0x9c, 0xca, 0xc8, 0x9c,
+        0xca, 0xc8, 0x9c, 0xca, 0x3c, 0x6e, 0x6c, 0xf3, 0xb5, 0x21, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn stored_block() {
+        assert_eq!(gunzip(STORED).unwrap(), b"unsafe { *p }");
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(gunzip(FIXED).unwrap(), b"unsafe { *p } unsafe { *q }");
+    }
+
+    #[test]
+    fn dynamic_block() {
+        assert_eq!(gunzip(DYNAMIC).unwrap(), b"abracadabra".repeat(3));
+    }
+
+    #[test]
+    fn members_are_concatenated() {
+        let data = [STORED, FIXED].concat();
+        assert_eq!(
+            gunzip(&data).unwrap(),
+            b"unsafe { *p }unsafe { *p } unsafe { *q }"
+        );
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let mut data = FIXED.to_vec();
+        let crc = data.len() - 8;
+        data[crc] ^= 1;
+        assert_eq!(gunzip(&data).unwrap_err(), "gzip checksum mismatch");
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(
+            gunzip(&DYNAMIC[..20]).unwrap_err(),
+            "truncated deflate stream"
+        );
+        assert_eq!(gunzip(&STORED[..20]).unwrap_err(), "truncated stored block");
+        assert_eq!(
+            gunzip(&FIXED[..FIXED.len() - 4]).unwrap_err(),
+            "truncated gzip trailer"
+        );
+        assert_eq!(gunzip(&FIXED[..6]).unwrap_err(), "truncated gzip header");
+    }
+
+    #[test]
+    fn output_is_limited() {
+        assert_eq!(gunzip_limited(FIXED, 27).unwrap().len(), 27);
+        assert_eq!(
+            gunzip_limited(FIXED, 26).unwrap_err(),
+            "decompressed size exceeds 26 bytes"
+        );
+        assert_eq!(
+            gunzip_limited(STORED, 12).unwrap_err(),
+            "decompressed size exceeds 12 bytes"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::{self, Display};
+
+use proc_macro2::LineColumn;
+use syn::{Generics, ItemImpl, ItemTrait, Token};
+
+use crate::pretty;
+
+pub enum UnsafeItemKind {
+    Impl {
+        trait_path: Option<String>,
+        self_ty: String,
+    },
+    Trait {
+        auto: bool,
+        name: String,
+        supertraits: String,
+    },
+}
+
+// An `unsafe impl` or `unsafe trait`. These contain no unsafe statements
+// themselves but carry safety obligations that need auditing.
+pub struct UnsafeItem {
+    pub kind: UnsafeItemKind,
+    pub generics: String,
+    pub where_clause: String,
+    pub start: LineColumn,
+}
+
+impl UnsafeItem {
+    pub fn from_impl(node: &ItemImpl) -> UnsafeItem {
+        let trait_path = node.trait_.as_ref().map(|(bang, path, _)| {
+            let bang = if bang.is_some() { "!" } else { "" };
+            format!("{}{}", bang, pretty::tokens(path))
+        });
+        UnsafeItem {
+            kind: UnsafeItemKind::Impl {
+                trait_path,
+                self_ty: pretty::tokens(&node.self_ty),
+            },
+            generics: generic_params(&node.generics),
+            where_clause: where_clause(&node.generics),
+            start: unsafe_start(&node.unsafety),
+        }
+    }
+
+    pub fn from_trait(node: &ItemTrait) -> UnsafeItem {
+        UnsafeItem {
+            kind: UnsafeItemKind::Trait {
+                auto: node.auto_token.is_some(),
+                name: node.ident.to_string(),
+                supertraits: pretty::tokens(&node.supertraits),
+            },
+            generics: generic_params(&node.generics),
+            where_clause: where_clause(&node.generics),
+            start: unsafe_start(&node.unsafety),
+        }
+    }
+
+    pub fn is_impl(&self) -> bool {
+        matches!(self.kind, UnsafeItemKind::Impl { .. })
+    }
+}
+
+fn unsafe_start(unsafety: &Option<Token![unsafe]>) -> LineColumn {
+    unsafety.as_ref().unwrap().span.start()
+}
+
+fn generic_params(generics: &Generics) -> String {
+    if generics.params.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", pretty::tokens(&generics.params))
+    }
+}
+
+fn where_clause(generics: &Generics) -> String {
+    match &generics.where_clause {
+        Some(clause) if !clause.predicates.is_empty() => {
+            format!(" {}", pretty::tokens(clause))
+        }
+        _ => String::new(),
+    }
+}
+
+impl Display for UnsafeItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            UnsafeItemKind::Impl {
+                trait_path: Some(trait_path),
+                self_ty,
+            } => write!(
+                f,
+                "unsafe impl{} {} for {}",
+                self.generics, trait_path, self_ty
+            )?,
+            UnsafeItemKind::Impl {
+                trait_path: None,
+                self_ty,
+            } => write!(f, "unsafe impl{} {}", self.generics, self_ty)?,
+            UnsafeItemKind::Trait {
+                auto,
+                name,
+                supertraits,
+            } => {
+                let auto = if *auto { "auto " } else { "" };
+                write!(f, "unsafe {}trait {}{}", auto, name, self.generics)?;
+                if !supertraits.is_empty() {
+                    write!(f, ": {}", supertraits)?;
+                }
+            }
+        }
+        f.write_str(&self.where_clause)
+    }
+}
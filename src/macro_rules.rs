@@ -0,0 +1,680 @@
+// An expander for the `macro_rules!` macros defined in the analysed crate.
+// Invocations are matched against each rule in turn, like rustc does, but
+// without hygiene: the expansion is plain tokens, with the tokens coming from
+// the macro definition respanned to the invocation site.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Delimiter, Group, Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+use syn::parse::{ParseStream, Parser};
+use syn::{
+    Block, Expr, File, ImplItem, Item, Lifetime, Lit, Meta, Pat, Path, Stmt, Token, Type,
+    Visibility,
+};
+
+// Nested invocations deeper than this are left unexpanded.
+pub const MAX_DEPTH: usize = 64;
+
+// Bounds the backtracking done while matching a single invocation.
+const MAX_STEPS: usize = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fragment {
+    Block,
+    Expr,
+    Ident,
+    Item,
+    Lifetime,
+    Literal,
+    Meta,
+    Pat,
+    PatParam,
+    Path,
+    Stmt,
+    Tt,
+    Ty,
+    Vis,
+}
+
+impl Fragment {
+    fn from_name(name: &str) -> Option<Fragment> {
+        match name {
+            "block" => Some(Fragment::Block),
+            "expr" | "expr_2021" => Some(Fragment::Expr),
+            "ident" => Some(Fragment::Ident),
+            "item" => Some(Fragment::Item),
+            "lifetime" => Some(Fragment::Lifetime),
+            "literal" => Some(Fragment::Literal),
+            "meta" => Some(Fragment::Meta),
+            "pat" => Some(Fragment::Pat),
+            "pat_param" => Some(Fragment::PatParam),
+            "path" => Some(Fragment::Path),
+            "stmt" => Some(Fragment::Stmt),
+            "tt" => Some(Fragment::Tt),
+            "ty" => Some(Fragment::Ty),
+            "vis" => Some(Fragment::Vis),
+            _ => None,
+        }
+    }
+
+    // The number of token trees at the start of `tokens` that parse as this
+    // fragment.
+    fn len(self, tokens: &[TokenTree]) -> Option<usize> {
+        match self {
+            Fragment::Tt => return (!tokens.is_empty()).then_some(1),
+            Fragment::Ident => {
+                return matches!(tokens.first(), Some(TokenTree::Ident(_))).then_some(1)
+            }
+            _ => {}
+        }
+        let stream: TokenStream = tokens.iter().cloned().collect();
+        let rest = |input: ParseStream| -> syn::Result<usize> {
+            match self {
+                Fragment::Block => {
+                    input.parse::<Block>()?;
+                }
+                Fragment::Expr => {
+                    input.parse::<Expr>()?;
+                }
+                Fragment::Item => {
+                    input.parse::<Item>()?;
+                }
+                Fragment::Lifetime => {
+                    input.parse::<Lifetime>()?;
+                }
+                Fragment::Literal => {
+                    if input.peek(Token![-]) {
+                        input.parse::<Token![-]>()?;
+                    }
+                    input.parse::<Lit>()?;
+                }
+                Fragment::Meta => {
+                    input.parse::<Meta>()?;
+                }
+                Fragment::Pat => {
+                    Pat::parse_multi_with_leading_vert(input)?;
+                }
+                Fragment::PatParam => {
+                    Pat::parse_single(input)?;
+                }
+                Fragment::Path => {
+                    input.parse::<Path>()?;
+                }
+                Fragment::Stmt => {
+                    input.parse::<Stmt>()?;
+                }
+                Fragment::Ty => {
+                    input.parse::<Type>()?;
+                }
+                Fragment::Vis => {
+                    input.parse::<Visibility>()?;
+                }
+                Fragment::Tt | Fragment::Ident => unreachable!(),
+            }
+            Ok(input.parse::<TokenStream>()?.into_iter().count())
+        };
+        let rest = rest.parse2(stream).ok()?;
+        let len = tokens.len() - rest;
+        // `vis` is the only fragment that may be empty.
+        (len > 0 || self == Fragment::Vis).then_some(len)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RepeatOp {
+    ZeroOrMore,
+    OneOrMore,
+    ZeroOrOne,
+}
+
+enum Matcher {
+    Token(TokenTree),
+    Group(Delimiter, Vec<Matcher>),
+    Fragment(String, Fragment),
+    Repeat {
+        body: Vec<Matcher>,
+        // Empty if the repetition has no separator.
+        separator: Vec<TokenTree>,
+        op: RepeatOp,
+    },
+}
+
+enum Transcriber {
+    Token(TokenTree),
+    Group(Delimiter, Vec<Transcriber>),
+    Var(Ident),
+    Repeat {
+        body: Vec<Transcriber>,
+        // Empty if the repetition has no separator.
+        separator: Vec<TokenTree>,
+    },
+}
+
+struct Rule {
+    matcher: Vec<Matcher>,
+    transcriber: Vec<Transcriber>,
+}
+
+#[derive(Clone)]
+enum Binding {
+    Tokens(Vec<TokenTree>, Fragment),
+    Repeated(Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+pub struct MacroRules {
+    rules: Vec<Rule>,
+}
+
+impl MacroRules {
+    // Parses the body of a `macro_rules!` definition, or returns `None` if it
+    // uses syntax the expander does not support.
+    pub fn parse(tokens: &TokenStream) -> Option<MacroRules> {
+        let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+        let mut rules = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let matcher = match &tokens[i] {
+                TokenTree::Group(group) => parse_matcher(group.stream())?,
+                _ => return None,
+            };
+            if !is_punct(tokens.get(i + 1), '=') || !is_punct(tokens.get(i + 2), '>') {
+                return None;
+            }
+            let transcriber = match tokens.get(i + 3) {
+                Some(TokenTree::Group(group)) => parse_transcriber(group.stream())?,
+                _ => return None,
+            };
+            rules.push(Rule {
+                matcher,
+                transcriber,
+            });
+            i += 4;
+            if is_punct(tokens.get(i), ';') {
+                i += 1;
+            }
+        }
+        Some(MacroRules { rules })
+    }
+
+    // Expands an invocation with the first rule whose matcher accepts its
+    // input, or returns `None` if no rule does.
+    pub fn expand(&self, input: &TokenStream, call_site: Span) -> Option<TokenStream> {
+        let input: Vec<TokenTree> = input.clone().into_iter().collect();
+        self.rules.iter().find_map(|rule| {
+            let mut steps = 0;
+            let bindings = match_all(&rule.matcher, &input, &mut steps)?;
+            let mut output = Vec::new();
+            transcribe(&rule.transcriber, &bindings, &[], call_site, &mut output)?;
+            Some(output.into_iter().collect())
+        })
+    }
+}
+
+// Parsers for an expansion, depending on where the macro was invoked.
+
+pub fn parse_stmts(tokens: TokenStream) -> Option<Vec<Stmt>> {
+    Block::parse_within.parse2(tokens).ok()
+}
+
+pub fn parse_expr(tokens: TokenStream) -> Option<Expr> {
+    syn::parse2(tokens).ok()
+}
+
+pub fn parse_items(tokens: TokenStream) -> Option<Vec<Item>> {
+    syn::parse2::<File>(tokens).ok().map(|file| file.items)
+}
+
+pub fn parse_impl_items(tokens: TokenStream) -> Option<Vec<ImplItem>> {
+    let items = |input: ParseStream| {
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(items)
+    };
+    items.parse2(tokens).ok()
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+// Parses the `sep? op` following a `$( ... )` repetition. Separators may
+// be multi-character punctuation like `::` or `=>`.
+fn parse_repeat_op(tokens: &[TokenTree], i: &mut usize) -> Option<(Vec<TokenTree>, RepeatOp)> {
+    let op = |token: Option<&TokenTree>| match token {
+        Some(TokenTree::Punct(punct)) => match punct.as_char() {
+            '*' => Some(RepeatOp::ZeroOrMore),
+            '+' => Some(RepeatOp::OneOrMore),
+            '?' => Some(RepeatOp::ZeroOrOne),
+            _ => None,
+        },
+        _ => None,
+    };
+    let mut end = *i;
+    loop {
+        if let Some(op) = op(tokens.get(end)) {
+            let mut separator = tokens[*i..end].to_vec();
+            // The last punctuation is joined to the repetition operator, not
+            // to what follows the separator in an expansion.
+            if let Some(TokenTree::Punct(punct)) = separator.last_mut() {
+                let mut alone = Punct::new(punct.as_char(), Spacing::Alone);
+                alone.set_span(punct.span());
+                *punct = alone;
+            }
+            *i = end + 1;
+            return Some((separator, op));
+        }
+        match tokens.get(end)? {
+            TokenTree::Punct(_) => end += 1,
+            _ if end == *i => end += 1,
+            _ => return None,
+        }
+    }
+}
+
+fn parse_matcher(stream: TokenStream) -> Option<Vec<Matcher>> {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut matchers = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if !is_punct(tokens.get(i), '$') {
+            matchers.push(match &tokens[i] {
+                TokenTree::Group(group) => {
+                    Matcher::Group(group.delimiter(), parse_matcher(group.stream())?)
+                }
+                token => Matcher::Token(token.clone()),
+            });
+            i += 1;
+            continue;
+        }
+        match tokens.get(i + 1) {
+            Some(TokenTree::Ident(name)) if is_punct(tokens.get(i + 2), ':') => {
+                let fragment = match tokens.get(i + 3)? {
+                    TokenTree::Ident(kind) => Fragment::from_name(&kind.to_string())?,
+                    _ => return None,
+                };
+                matchers.push(Matcher::Fragment(name.to_string(), fragment));
+                i += 4;
+            }
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                let body = parse_matcher(group.stream())?;
+                i += 2;
+                let (separator, op) = parse_repeat_op(&tokens, &mut i)?;
+                matchers.push(Matcher::Repeat {
+                    body,
+                    separator,
+                    op,
+                });
+            }
+            Some(TokenTree::Ident(_)) => return None,
+            // A `$` that starts no fragment or repetition is matched as is.
+            _ => {
+                matchers.push(Matcher::Token(tokens[i].clone()));
+                i += 1;
+            }
+        }
+    }
+    Some(matchers)
+}
+
+fn parse_transcriber(stream: TokenStream) -> Option<Vec<Transcriber>> {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut transcribers = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if !is_punct(tokens.get(i), '$') {
+            transcribers.push(match &tokens[i] {
+                TokenTree::Group(group) => {
+                    Transcriber::Group(group.delimiter(), parse_transcriber(group.stream())?)
+                }
+                token => Transcriber::Token(token.clone()),
+            });
+            i += 1;
+            continue;
+        }
+        match tokens.get(i + 1) {
+            // Paths through `$crate` resolve within the defining crate, which
+            // is the analysed one.
+            Some(TokenTree::Ident(name)) if name == "crate" => {
+                transcribers.push(Transcriber::Token(TokenTree::Ident(name.clone())));
+                i += 2;
+            }
+            Some(TokenTree::Ident(name)) => {
+                transcribers.push(Transcriber::Var(name.clone()));
+                i += 2;
+            }
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                let body = parse_transcriber(group.stream())?;
+                i += 2;
+                let (separator, _) = parse_repeat_op(&tokens, &mut i)?;
+                transcribers.push(Transcriber::Repeat { body, separator });
+            }
+            _ => {
+                transcribers.push(Transcriber::Token(tokens[i].clone()));
+                i += 1;
+            }
+        }
+    }
+    Some(transcribers)
+}
+
+fn same_token(a: &TokenTree, b: &TokenTree) -> bool {
+    match (a, b) {
+        (TokenTree::Ident(a), TokenTree::Ident(b)) => a == b,
+        (TokenTree::Punct(a), TokenTree::Punct(b)) => a.as_char() == b.as_char(),
+        (TokenTree::Literal(a), TokenTree::Literal(b)) => a.to_string() == b.to_string(),
+        _ => false,
+    }
+}
+
+// Matches the whole of `tokens`.
+fn match_all(matchers: &[Matcher], tokens: &[TokenTree], steps: &mut usize) -> Option<Bindings> {
+    match_prefix(matchers, tokens, steps, &mut |len, _| len == tokens.len())
+}
+
+// Matches `matchers` against a prefix of `tokens`, trying longer
+// repetitions first, until `accept` is satisfied with the length matched.
+fn match_prefix(
+    matchers: &[Matcher],
+    tokens: &[TokenTree],
+    steps: &mut usize,
+    accept: &mut dyn FnMut(usize, &Bindings) -> bool,
+) -> Option<Bindings> {
+    *steps += 1;
+    if *steps > MAX_STEPS {
+        return None;
+    }
+    let (first, rest) = match matchers.split_first() {
+        Some(split) => split,
+        None => {
+            let bindings = Bindings::new();
+            return accept(0, &bindings).then_some(bindings);
+        }
+    };
+
+    let mut continue_with = |len: usize, mut bindings: Bindings, steps: &mut usize| {
+        let mut accept_rest = |rest_len: usize, rest_bindings: &Bindings| {
+            let mut all = bindings.clone();
+            all.extend(rest_bindings.clone());
+            accept(len + rest_len, &all)
+        };
+        let rest_bindings = match_prefix(rest, &tokens[len..], steps, &mut accept_rest)?;
+        bindings.extend(rest_bindings);
+        Some(bindings)
+    };
+
+    match first {
+        Matcher::Token(expected) => {
+            if !same_token(expected, tokens.first()?) {
+                return None;
+            }
+            continue_with(1, Bindings::new(), steps)
+        }
+        Matcher::Group(delimiter, inner) => match tokens.first()? {
+            TokenTree::Group(group) if group.delimiter() == *delimiter => {
+                let inner_tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+                let bindings = match_all(inner, &inner_tokens, steps)?;
+                continue_with(1, bindings, steps)
+            }
+            _ => None,
+        },
+        Matcher::Fragment(name, fragment) => {
+            let len = fragment.len(tokens)?;
+            let mut bindings = Bindings::new();
+            let matched = tokens[..len].to_vec();
+            bindings.insert(name.clone(), Binding::Tokens(matched, *fragment));
+            continue_with(len, bindings, steps)
+        }
+        Matcher::Repeat {
+            body,
+            separator,
+            op,
+        } => {
+            // Every way of matching successive iterations, longest first.
+            let mut iterations: Vec<(usize, Vec<Bindings>)> = vec![(0, Vec::new())];
+            loop {
+                let (len, done) = iterations.last().unwrap().clone();
+                if *op == RepeatOp::ZeroOrOne && !done.is_empty() {
+                    break;
+                }
+                let mut start = len;
+                if !done.is_empty() {
+                    let found = tokens.get(start..start + separator.len());
+                    match found {
+                        Some(found)
+                            if found.iter().zip(separator).all(|(a, b)| same_token(a, b)) =>
+                        {
+                            start += separator.len()
+                        }
+                        _ => break,
+                    }
+                }
+                let mut body_len = 0;
+                let next = match_prefix(body, &tokens[start..], steps, &mut |len, _| {
+                    body_len = len;
+                    len > 0
+                });
+                match next {
+                    Some(bindings) => {
+                        let mut done = done;
+                        done.push(bindings);
+                        iterations.push((start + body_len, done));
+                    }
+                    None => break,
+                }
+            }
+            let min = if *op == RepeatOp::OneOrMore { 1 } else { 0 };
+            for (len, done) in iterations.into_iter().rev() {
+                if done.len() < min {
+                    continue;
+                }
+                let bindings = repeated(body, done);
+                if let Some(bindings) = continue_with(len, bindings, steps) {
+                    return Some(bindings);
+                }
+            }
+            None
+        }
+    }
+}
+
+// Collects the bindings of each iteration into one `Repeated` binding per
+// variable of the repetition body.
+fn repeated(body: &[Matcher], iterations: Vec<Bindings>) -> Bindings {
+    let mut names = Vec::new();
+    collect_names(body, &mut names);
+    names
+        .into_iter()
+        .map(|name| {
+            let values = iterations
+                .iter()
+                .filter_map(|bindings| bindings.get(&name).cloned())
+                .collect();
+            (name, Binding::Repeated(values))
+        })
+        .collect()
+}
+
+fn collect_names(matchers: &[Matcher], names: &mut Vec<String>) {
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => {}
+            Matcher::Group(_, inner) | Matcher::Repeat { body: inner, .. } => {
+                collect_names(inner, names)
+            }
+            Matcher::Fragment(name, _) => names.push(name.clone()),
+        }
+    }
+}
+
+// Looks up a variable at the current repetition indices.
+fn lookup<'b>(bindings: &'b Bindings, name: &str, indices: &[usize]) -> Option<&'b Binding> {
+    let mut binding = bindings.get(name)?;
+    for &index in indices {
+        match binding {
+            Binding::Repeated(values) => binding = values.get(index)?,
+            Binding::Tokens(..) => break,
+        }
+    }
+    Some(binding)
+}
+
+// The number of iterations of a repetition, from the variables it uses.
+fn repeat_count(body: &[Transcriber], bindings: &Bindings, indices: &[usize]) -> Option<usize> {
+    let mut count = None;
+    for transcriber in body {
+        let n = match transcriber {
+            Transcriber::Token(_) => None,
+            Transcriber::Group(_, inner) => repeat_count(inner, bindings, indices),
+            Transcriber::Var(name) => match lookup(bindings, &name.to_string(), indices) {
+                Some(Binding::Repeated(values)) => Some(values.len()),
+                _ => None,
+            },
+            Transcriber::Repeat { body, .. } => repeat_count(body, bindings, indices),
+        };
+        match (count, n) {
+            (Some(count), Some(n)) if count != n => return None,
+            (None, Some(n)) => count = Some(n),
+            _ => {}
+        }
+    }
+    count
+}
+
+fn respan(token: &TokenTree, span: Span) -> TokenTree {
+    let mut token = token.clone();
+    token.set_span(span);
+    token
+}
+
+fn transcribe(
+    transcribers: &[Transcriber],
+    bindings: &Bindings,
+    indices: &[usize],
+    call_site: Span,
+    output: &mut Vec<TokenTree>,
+) -> Option<()> {
+    for transcriber in transcribers {
+        match transcriber {
+            Transcriber::Token(token) => output.push(respan(token, call_site)),
+            Transcriber::Group(delimiter, inner) => {
+                let mut tokens = Vec::new();
+                transcribe(inner, bindings, indices, call_site, &mut tokens)?;
+                let mut group = Group::new(*delimiter, tokens.into_iter().collect());
+                group.set_span(call_site);
+                output.push(TokenTree::Group(group));
+            }
+            Transcriber::Var(name) => match lookup(bindings, &name.to_string(), indices) {
+                // Expressions are kept together as in rustc, so that
+                // `$a * 2` with `$a = 1 + 1` keeps its meaning.
+                Some(Binding::Tokens(tokens, Fragment::Expr)) => {
+                    let group = Group::new(Delimiter::None, tokens.iter().cloned().collect());
+                    output.push(TokenTree::Group(group));
+                }
+                Some(Binding::Tokens(tokens, _)) => output.extend(tokens.iter().cloned()),
+                Some(Binding::Repeated(_)) => return None,
+                // Not a variable of this rule, so passed through as written.
+                None => {
+                    output.push(TokenTree::Punct(Punct::new('$', Spacing::Alone)));
+                    output.push(respan(&TokenTree::Ident(name.clone()), call_site));
+                }
+            },
+            Transcriber::Repeat { body, separator } => {
+                let count = repeat_count(body, bindings, indices)?;
+                for i in 0..count {
+                    if i > 0 {
+                        for token in separator {
+                            output.push(respan(token, call_site));
+                        }
+                    }
+                    let mut indices = indices.to_vec();
+                    indices.push(i);
+                    transcribe(body, bindings, &indices, call_site, output)?;
+                }
+            }
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(rules: &str, input: &str) -> Option<String> {
+        let rules = MacroRules::parse(&rules.parse().unwrap()).unwrap();
+        let output = rules.expand(&input.parse().unwrap(), Span::call_site())?;
+        Some(output.to_string())
+    }
+
+    #[test]
+    fn rules_are_tried_in_order() {
+        let rules = "() => { none }; ($e:expr) => { one($e) }; ($($t:tt)*) => { many }";
+        assert_eq!(expand(rules, "").as_deref(), Some("none"));
+        assert_eq!(expand(rules, "1 + 2").as_deref(), Some("one (1 + 2)"));
+        assert_eq!(expand(rules, "1 2").as_deref(), Some("many"));
+    }
+
+    #[test]
+    fn separators() {
+        let rules = "($($a:ident),* ; $($b:literal)=>+) => { [$($a)|*] [$($b),+] }";
+        assert_eq!(
+            expand(rules, "x, y, z; 1 => 2").as_deref(),
+            Some("[x | y | z] [1 , 2]")
+        );
+        assert_eq!(expand(rules, "; 1").as_deref(), Some("[] [1]"));
+        // A trailing separator is not part of the repetition.
+        assert_eq!(expand(rules, "x,; 1"), None);
+        // `+` needs at least one iteration.
+        assert_eq!(expand(rules, "x;"), None);
+    }
+
+    #[test]
+    fn nested_repetitions() {
+        let rules = "($($name:ident { $($field:ident: $ty:ty),* })*) => {
+            $(struct $name { $($field: $ty),* })*
+        }";
+        assert_eq!(
+            expand(rules, "A { x: u8, y: *const u8 } B {}").as_deref(),
+            Some("struct A { x : u8 , y : * const u8 } struct B { }")
+        );
+    }
+
+    #[test]
+    fn mismatched_repetitions_do_not_expand() {
+        let rules = "($($a:ident)* ; $($b:ident)*) => { $(($a, $b))* }";
+        assert_eq!(
+            expand(rules, "x y; z w").as_deref(),
+            Some("(x , z) (y , w)")
+        );
+        assert_eq!(expand(rules, "x y; z"), None);
+    }
+
+    #[test]
+    fn raw_identifiers() {
+        let rules = "($r#type:ident) => { let r#fn = $r#type; $unbound; $r#unbound }";
+        assert_eq!(
+            expand(rules, "r#match").as_deref(),
+            Some("let r#fn = r#match ; $ unbound ; $ r#unbound")
+        );
+    }
+
+    #[test]
+    fn backtracking_is_bounded() {
+        let rules = "($($a:tt)* $($b:tt)* $($c:tt)* end) => { matched }";
+        assert_eq!(expand(rules, "1 2 3 end").as_deref(), Some("matched"));
+        let rules = MacroRules::parse(&rules.parse().unwrap()).unwrap();
+        let input: Vec<TokenTree> = "1 "
+            .repeat(40)
+            .parse::<TokenStream>()
+            .unwrap()
+            .into_iter()
+            .collect();
+        let mut steps = 0;
+        assert!(match_all(&rules.rules[0].matcher, &input, &mut steps).is_none());
+        assert!(steps > MAX_STEPS);
+    }
+}
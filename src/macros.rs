@@ -0,0 +1,214 @@
+// Best-effort parsing of macro invocations whose input is ordinary Rust
+// syntax, so that the code passed to them can be visited like any other.
+// Macros other than the well-known std ones can be declared in the
+// `[macros]` section of the configuration file:
+//
+//     [macros]
+//     my_assert = "exprs"
+//     with_lock = "stmts"
+//     declare_globals = "items"
+//
+// The branches of `cfg_if!` are parsed as items, each behind the `cfg` that
+// selects it.
+
+use std::collections::HashMap;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{ParseStream, Parser};
+use syn::{braced, Attribute, Block, Expr, File, Ident, Item, Macro, Meta, Stmt, Token};
+
+use crate::toml;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MacroInput {
+    // Comma separated expressions, optionally `name = expr` as in format
+    // arguments, or `elem; n` as in `vec!`.
+    Exprs,
+    // The contents of a block.
+    Stmts,
+    // Items, including the `static ref` declarations of `lazy_static!`.
+    Items,
+    // `if #[cfg(...)] { items } else { items }` chains.
+    CfgIf,
+}
+
+impl MacroInput {
+    fn from_name(name: &str) -> Option<MacroInput> {
+        match name {
+            "exprs" => Some(MacroInput::Exprs),
+            "stmts" => Some(MacroInput::Stmts),
+            "items" => Some(MacroInput::Items),
+            _ => None,
+        }
+    }
+}
+
+const EXPR_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "dbg",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "eprint",
+    "eprintln",
+    "format",
+    "format_args",
+    "panic",
+    "print",
+    "println",
+    "todo",
+    "unimplemented",
+    "unreachable",
+    "vec",
+    "write",
+    "writeln",
+];
+
+const ITEM_MACROS: &[&str] = &["lazy_static", "thread_local"];
+
+pub enum MacroBody {
+    Exprs(Vec<Expr>),
+    Stmts(Vec<Stmt>),
+    Items(Vec<Item>),
+}
+
+pub struct Macros {
+    inputs: HashMap<String, MacroInput>,
+}
+
+impl Macros {
+    pub fn new(config: Option<&toml::Table>) -> Result<Macros, String> {
+        let mut inputs = HashMap::new();
+        for name in EXPR_MACROS {
+            inputs.insert(name.to_string(), MacroInput::Exprs);
+        }
+        for name in ITEM_MACROS {
+            inputs.insert(name.to_string(), MacroInput::Items);
+        }
+        inputs.insert("cfg_if".to_string(), MacroInput::CfgIf);
+
+        let section = match config.and_then(|config| config.get("macros")) {
+            Some(section) => section
+                .as_table()
+                .ok_or("`macros` must be a table of macro names")?,
+            None => return Ok(Macros { inputs }),
+        };
+        for (name, input) in section {
+            let input = input
+                .as_str()
+                .and_then(MacroInput::from_name)
+                .ok_or_else(|| {
+                    format!(
+                        "`macros.{}` must be one of \"exprs\", \"stmts\" or \"items\"",
+                        name
+                    )
+                })?;
+            inputs.insert(name.clone(), input);
+        }
+        Ok(Macros { inputs })
+    }
+
+    // Parses the input of a known macro, or returns `None` if the macro is
+    // unknown or its input does not parse as expected.
+    pub fn parse(&self, mac: &Macro) -> Option<MacroBody> {
+        let name = mac.path.segments.last()?.ident.to_string();
+        let tokens = mac.tokens.clone();
+        match self.inputs.get(&name)? {
+            MacroInput::Exprs => exprs.parse2(tokens).ok().map(MacroBody::Exprs),
+            MacroInput::Stmts => Block::parse_within
+                .parse2(tokens)
+                .ok()
+                .map(MacroBody::Stmts),
+            MacroInput::Items => {
+                let file: File = syn::parse2(strip_static_ref(tokens)).ok()?;
+                Some(MacroBody::Items(file.items))
+            }
+            MacroInput::CfgIf => cfg_if.parse2(tokens).ok().map(MacroBody::Items),
+        }
+    }
+}
+
+fn exprs(input: ParseStream) -> syn::Result<Vec<Expr>> {
+    let mut exprs = Vec::new();
+    while !input.is_empty() {
+        if input.peek(Ident) && input.peek2(Token![=]) {
+            input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+        }
+        exprs.push(input.parse()?);
+        if input.is_empty() {
+            break;
+        }
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        } else {
+            input.parse::<Token![,]>()?;
+        }
+    }
+    Ok(exprs)
+}
+
+// The items of every branch of `cfg_if!`, each given the `cfg` of its
+// branch and the negation of those of the branches before it.
+fn cfg_if(input: ParseStream) -> syn::Result<Vec<Item>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        let mut previous: Vec<Meta> = Vec::new();
+        loop {
+            let predicate = if input.peek(Token![if]) {
+                input.parse::<Token![if]>()?;
+                let attr = input.call(Attribute::parse_outer)?;
+                match attr.as_slice() {
+                    [attr] if attr.path().is_ident("cfg") => {
+                        Some(attr.meta.require_list()?.parse_args::<Meta>()?)
+                    }
+                    _ => return Err(input.error("expected `#[cfg(...)]`")),
+                }
+            } else {
+                None
+            };
+            let not_previous = match previous.as_slice() {
+                [one] => quote!(not(#one)),
+                _ => quote!(not(any(#(#previous),*))),
+            };
+            let cfg = match (&predicate, previous.as_slice()) {
+                (Some(predicate), []) => quote!(#predicate),
+                (Some(predicate), _) => quote!(all(#predicate, #not_previous)),
+                (None, _) => not_previous,
+            };
+            let content;
+            braced!(content in input);
+            let branch: File = content.parse()?;
+            for item in branch.items {
+                items.push(syn::parse2(quote!(#[cfg(#cfg)] #item))?);
+            }
+            match predicate {
+                Some(predicate) if input.peek(Token![else]) => {
+                    input.parse::<Token![else]>()?;
+                    previous.push(predicate);
+                }
+                _ => break,
+            }
+        }
+    }
+    Ok(items)
+}
+
+// Turns `static ref NAME: T = ...;` into a plain static.
+fn strip_static_ref(tokens: TokenStream) -> TokenStream {
+    let mut stripped = Vec::new();
+    for token in tokens {
+        let after_static = matches!(
+            stripped.last(),
+            Some(TokenTree::Ident(ident)) if ident == "static"
+        );
+        match &token {
+            TokenTree::Ident(ident) if after_static && ident == "ref" => {}
+            _ => stripped.push(token),
+        }
+    }
+    stripped.into_iter().collect()
+}
@@ -1,90 +1,939 @@
-use colored::Colorize;
+mod archive;
+mod catalog;
+mod cfg;
+mod config;
+mod deps;
+mod diagnostic;
+mod diff;
+mod ffi;
+mod files;
+mod glob;
+mod inflate;
+mod inventory;
+mod macro_rules;
+mod macros;
+mod manifest;
+mod modules;
+mod options;
+mod package;
+mod pretty;
+mod recover;
+mod role;
+mod symbols;
+mod toml;
+mod unsafe_macros;
+mod unsafe_ops;
+mod unused_unsafe;
+mod visitor;
+mod walk;
+
 use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::ffi::OsStr;
 use std::fmt::{self, Display};
-use std::fs::File;
-use std::io::Read;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, Read};
+use std::mem;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::str::Utf8Error;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use syn::visit::{self, Visit};
-use syn::{ExprUnsafe, ItemFn, Stmt};
+use archive::Archive;
+use catalog::Catalog;
+use cfg::{Cfg, CfgSet};
+use deps::Locked;
+use diagnostic::{Diagnostic, Level};
+use diff::{Change, Region, RegionKind};
+use files::Files;
+use macros::Macros;
+use manifest::Edition;
+use modules::ModuleFile;
+use options::Options;
+use package::{Package, TargetKind};
+use recover::Skipped;
+use role::Role;
+use symbols::SymbolTable;
+use syn::visit::Visit;
+use unsafe_macros::UnsafeMacro;
+use visitor::{Accounting, Stats, StmtVisitor};
 
-struct StmtVisitor {
-    count: usize,
-    unsafe_count: usize,
-    in_unsafe: u32,
-}
+fn main() {
+    let (options, catalog, macros) = match setup() {
+        Ok(setup) => setup,
+        Err(message) => {
+            diagnostic::eprint(Error::Config { message });
+            process::exit(2);
+        }
+    };
+
+    if options.inputs.is_empty() {
+        println!("no input provided");
+        return;
+    }
+    if options.diff {
+        diff(&options, &macros);
+        exit_status();
+        return;
+    }
+
+    // Parse every file and collect the declarations of each crate first, so
+    // that calls into other files of the same crate can be resolved.
+    let mut inputs = Inputs::load(&options, &options.inputs);
+
+    // The packages given are followed by the libraries of their locked
+    // dependencies whose sources are present locally.
+    let roots = inputs.packages.len();
+    let mut trees = Vec::new();
+    if options.deps {
+        for (manifest, members) in mem::take(&mut inputs.manifests) {
+            if let Some(tree) = dependency_tree(&manifest, members, &mut inputs.packages) {
+                trees.push(tree);
+            }
+        }
+    }
+
+    inputs.add_targets(&options, &macros);
+    let Inputs {
+        crates, packages, ..
+    } = inputs;
+
+    let Crates {
+        sources,
+        mut tables,
+        cfgs,
+        invocations,
+        mut unsafe_macros,
+        ..
+    } = crates;
+    // Dependencies only show up in the dependency tree.
+    let dependencies: HashSet<usize> = sources
+        .iter()
+        .filter(|source| source.target.is_some_and(|(p, _)| p >= roots))
+        .map(|source| source.krate)
+        .collect();
+    unsafe_macros.retain(|(_, krate, _)| !dependencies.contains(krate));
+    for source in &sources {
+        let cfg = &cfgs[source.krate];
+        tables[source.krate].add_file(&source.ast, &source.module, cfg);
+    }
+
+    let mut reports = Vec::new();
+    for source in &sources {
+        let mut visitor = StmtVisitor::new(
+            Accounting::for_edition(source.edition),
+            &tables[source.krate],
+            &catalog,
+            &macros,
+            source.module.clone(),
+            &cfgs[source.krate],
+            options.by_cfg,
+        );
+        visitor.file_cfgs = source.cfgs.clone();
+        visitor.role = source.role;
+        visitor.production_only = options.production_only;
+        visitor.visit_file(&source.ast);
+        reports.push((source, visitor));
+    }
 
-impl<'ast> Visit<'ast> for StmtVisitor {
-    fn visit_expr_unsafe(&mut self, node: &'ast ExprUnsafe) {
-        self.in_unsafe += 1;
-        visit::visit_expr_unsafe(self, node);
-        self.in_unsafe -= 1;
+    let mut totals = Totals::default();
+    for (source, visitor) in reports.iter().filter(|(source, _)| source.target.is_none()) {
+        print_file(&options, "", source, visitor, &mut totals);
     }
-    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
-        let unsafety = node.sig.unsafety.is_some();
-        if unsafety {
-            self.in_unsafe += 1;
+    for (p, package) in packages[..roots].iter().enumerate() {
+        let in_package = |source: &Source| source.target.is_some_and(|(i, _)| i == p);
+        let stats = sum_stats(reports.iter().filter(|(source, _)| in_package(source)));
+        println!("package `{}`: {}", package.name, stats);
+        print_ops("  ", &stats);
+        for (t, target) in package.targets.iter().enumerate() {
+            if options.production_only && Role::for_target(target.kind) != Role::Production {
+                continue;
+            }
+            let in_target = |source: &Source| source.target == Some((p, t));
+            let files: Vec<_> = reports
+                .iter()
+                .filter(|(source, _)| in_target(source))
+                .collect();
+            let stats = sum_stats(files.iter().copied());
+            println!("  {}: {}", target, stats);
+            print_ops("    ", &stats);
+            for (source, visitor) in files {
+                print_file(&options, "    ", source, visitor, &mut totals);
+            }
         }
-        visit::visit_item_fn(self, node);
-        if unsafety {
-            self.in_unsafe -= 1;
+    }
+
+    println!("total: {}", totals.stats);
+    print_ops("", &totals.stats);
+    if totals.unsafe_impls + totals.unsafe_traits > 0 {
+        println!(
+            "unsafe impls: {}, unsafe traits: {}",
+            totals.unsafe_impls, totals.unsafe_traits
+        );
+    }
+    if options.ffi {
+        println!(
+            "ffi imports: {}, ffi exports: {}",
+            totals.ffi_imports, totals.ffi_exports
+        );
+    }
+    if options.unused_unsafe {
+        println!(
+            "unused unsafe blocks: {}, unused unsafe fns: {}",
+            totals.unused_blocks, totals.unused_fns
+        );
+    }
+    // Only worth a breakdown if the code has more than one role.
+    if totals.role_stats.len() > 1 {
+        println!("by role:");
+        for (role, stats) in &totals.role_stats {
+            let excluded = options.production_only && *role != Role::Production;
+            let note = if excluded {
+                " (excluded from total)"
+            } else {
+                ""
+            };
+            println!("  {}: {}{}", role, stats, note);
+            print_ops("    ", stats);
         }
     }
-    fn visit_stmt(&mut self, node: &'ast Stmt) {
-        self.count += 1;
-        if self.in_unsafe > 0 {
-            self.unsafe_count += 1;
+    if options.by_cfg {
+        println!("by cfg:");
+        for (cfg, stats) in &totals.cfg_stats {
+            let cfg = cfg.as_deref().unwrap_or("unconditional");
+            println!("  {}: {}", cfg, stats);
+            print_ops("    ", stats);
         }
-        visit::visit_stmt(self, node);
     }
+    if options.by_function {
+        let mut functions = Vec::new();
+        for (source, visitor) in &reports {
+            if source.target.is_some_and(|(p, _)| p >= roots) {
+                continue;
+            }
+            let functions_in = visitor
+                .functions
+                .iter()
+                .filter(|function| !options.production_only || function.role == Role::Production);
+            functions.extend(functions_in.map(|function| (source, function)));
+        }
+        // Most unsafe first, and otherwise in the order they were found.
+        functions.sort_by_key(|(_, function)| Reverse(function.unsafe_count));
+        println!("by function:");
+        for (source, function) in functions {
+            // Paths start with the name of the target, as in `diff`.
+            let mut path = function.path.clone();
+            if let Some((p, t)) = source.target {
+                path[0] = packages[p].targets[t].name.clone();
+            }
+            let keyword = if function.declared_unsafe {
+                "unsafe fn"
+            } else {
+                "fn"
+            };
+            println!(
+                "  {}:{}:{}: {} {}: {}",
+                source.filename,
+                function.start.line,
+                function.start.column + 1,
+                keyword,
+                path.join("::"),
+                function
+            );
+        }
+    }
+    if !unsafe_macros.is_empty() {
+        println!("macros that emit unsafe:");
+        for (filename, krate, mut definition) in unsafe_macros {
+            definition.invocations = invocations[krate]
+                .get(&definition.name)
+                .copied()
+                .unwrap_or(0);
+            println!(
+                "  {}:{}:{}: {}",
+                filename,
+                definition.start.line,
+                definition.start.column + 1,
+                definition
+            );
+        }
+    }
+    if !trees.is_empty() {
+        println!("dependencies:");
+        for tree in &trees {
+            for &root in &tree.roots {
+                let mut seen = HashSet::new();
+                print_dependency(tree, root, &reports, "  ", "", &mut seen);
+            }
+        }
+    }
+    exit_status();
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// The files given as inputs and the packages of the manifests and archives
+// given, whose targets are only parsed by `add_targets`.
+#[derive(Default)]
+struct Inputs {
+    crates: Crates,
+    files: Files,
+    packages: Vec<Package>,
+    // The manifests given, with the range of `packages` each one loaded.
+    manifests: Vec<(PathBuf, Range<usize>)>,
+}
 
-    if args.len() <= 1 {
-        println!("no input provided");
-        return;
+impl Inputs {
+    fn load(options: &Options, inputs: &[String]) -> Inputs {
+        let mut loaded = Inputs::default();
+        for input in inputs {
+            let path = Path::new(input);
+            // Archives are read into memory and analysed as the package whose
+            // manifest is closest to their root.
+            let manifest = if archive::is_archive(path) {
+                let archive = match Archive::open(path) {
+                    Ok(archive) => archive,
+                    Err(message) => {
+                        report(Error::Archive { message });
+                        continue;
+                    }
+                };
+                let manifest = archive
+                    .paths()
+                    .filter(|entry| entry.file_name() == Some(OsStr::new("Cargo.toml")))
+                    .min_by_key(|entry| entry.components().count())
+                    .map(|entry| path.join(entry));
+                loaded.files.add_archive(path.to_path_buf(), archive);
+                match manifest {
+                    Some(manifest) => Cow::Owned(manifest),
+                    None => {
+                        let message = format!("{}: no `Cargo.toml` in archive", path.display());
+                        report(Error::Archive { message });
+                        continue;
+                    }
+                }
+            } else {
+                Cow::Borrowed(path)
+            };
+            if manifest.file_name() == Some(OsStr::new("Cargo.toml")) {
+                match package::load(&loaded.files, &manifest) {
+                    Ok(manifest_packages) => {
+                        let members =
+                            loaded.packages.len()..loaded.packages.len() + manifest_packages.len();
+                        loaded.manifests.push((manifest.to_path_buf(), members));
+                        loaded.packages.extend(manifest_packages);
+                    }
+                    Err(message) => {
+                        report(Error::Manifest { message });
+                    }
+                }
+                continue;
+            }
+            // `-` is the source read from stdin, reported under the name
+            // given by `--stdin-filename`.
+            let walked = if input == "-" {
+                let name = options.stdin_filename.as_deref().unwrap_or("<stdin>");
+                let mut src = String::new();
+                io::stdin()
+                    .read_to_string(&mut src)
+                    .map(|_| loaded.files.add_buffer(PathBuf::from(name), src))
+                    .map(|_| vec![PathBuf::from(name)])
+                    .map_err(|error| (error, name))
+            } else {
+                let mut errors = Vec::new();
+                let walked = walk::rust_files(path, &options.filters, &mut errors);
+                for (filepath, error) in errors {
+                    report(Error::Io { error, filepath });
+                }
+                walked.map_err(|error| (error, input.as_str()))
+            };
+            let paths = match walked {
+                Ok(paths) => paths,
+                Err((error, filepath)) => {
+                    report(Error::Io {
+                        error,
+                        filepath: PathBuf::from(filepath),
+                    });
+                    continue;
+                }
+            };
+            for file in paths {
+                let filename = file.to_string_lossy().into_owned();
+                let (src, ast) = match parse(&loaded.files, &filename) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                // Files outside of any Cargo package are treated as crate roots
+                // and keep the pre-2024 accounting.
+                let location = manifest::locate(&file);
+                let edition = options
+                    .edition
+                    .or(location.as_ref().map(|location| location.edition))
+                    .unwrap_or(Edition::E2021);
+                let role = match &location {
+                    Some(location) => location.role,
+                    None => Role::for_path(&file, false),
+                };
+                if options.production_only && role != Role::Production {
+                    continue;
+                }
+                let (package_dir, crate_name, module, cfg) = match location {
+                    Some(location) => {
+                        let cfg = options.cfg.for_package(
+                            &location.crate_name,
+                            &location.features,
+                            options.default_features,
+                        );
+                        (
+                            Some(location.package_dir),
+                            Some(location.crate_name),
+                            location.module,
+                            cfg,
+                        )
+                    }
+                    None => (None, None, vec!["crate".to_string()], options.cfg.clone()),
+                };
+                let krate = loaded.crates.index(package_dir, crate_name, cfg);
+                loaded.crates.add(Source {
+                    filename,
+                    src,
+                    ast,
+                    edition,
+                    module,
+                    cfgs: Vec::new(),
+                    role,
+                    krate,
+                    target: None,
+                });
+            }
+        }
+        loaded
     }
 
-    let mut total = 0;
-    let mut unsafe_total = 0;
-    for filename in &args[1..] {
-        let mut src = String::new();
-        let mut file = File::open(filename).expect("Unable to open source file");
-        file.read_to_string(&mut src)
-            .expect("Unable to read input file");
-
-        let ast = match syn::parse_file(&src) {
-            Err(error) => {
-                let err = Error::ParseFile {
-                    error,
-                    filepath: PathBuf::from(filename),
-                    source_code: src,
-                };
-                let _ = writeln!(io::stderr(), "{}", err);
+    // The files of a package's targets are those reachable from each crate
+    // root through `mod` declarations.
+    fn add_targets(&mut self, options: &Options, macros: &Macros) {
+        for (p, package) in self.packages.iter().enumerate() {
+            let package_dir = self.files.canonicalize(&package.dir);
+            let cfg =
+                options
+                    .cfg
+                    .for_package(&package.name, &package.features, options.default_features);
+            let krate = self
+                .crates
+                .index(Some(package_dir), Some(package.crate_name.clone()), cfg);
+            for (t, target) in package.targets.iter().enumerate() {
+                let role = Role::for_target(target.kind);
+                if options.production_only && role != Role::Production {
+                    continue;
+                }
+                let mut visited = HashSet::new();
+                let mut rules = HashMap::new();
+                let mut pending = vec![ModuleFile::root(target.root.clone())];
+                while let Some(file) = pending.pop() {
+                    // Modules including each other through `#[path]` are an
+                    // error in rustc, and are only visited once here.
+                    if !visited.insert(file.path.clone()) {
+                        continue;
+                    }
+                    let filename = file.path.to_string_lossy().into_owned();
+                    let (src, ast) = match parse(&self.files, &filename) {
+                        Some(parsed) => parsed,
+                        None => continue,
+                    };
+                    let cfg = &self.crates.cfgs[krate];
+                    let submodules = file.submodules(&self.files, &ast, cfg, macros, &mut rules);
+                    pending.extend(submodules.into_iter().rev());
+                    self.crates.add(Source {
+                        filename,
+                        src,
+                        ast,
+                        edition: options.edition.unwrap_or(target.edition),
+                        module: file.module,
+                        cfgs: file.cfgs,
+                        role,
+                        krate,
+                        target: Some((p, t)),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// The packages of a `Cargo.lock`, each with the index of its package in the
+// analysis if its sources were found.
+struct DependencyTree {
+    locked: Vec<Locked>,
+    packages: Vec<Option<usize>>,
+    // The locked packages that were given as inputs.
+    roots: Vec<usize>,
+}
+
+fn dependency_tree(
+    manifest: &Path,
+    members: Range<usize>,
+    packages: &mut Vec<Package>,
+) -> Option<DependencyTree> {
+    let lockfile = match deps::find_lockfile(manifest) {
+        Some(lockfile) => lockfile,
+        None => {
+            let message = format!("for {}: no `Cargo.lock` found", manifest.display());
+            report(Error::Lockfile { message });
+            return None;
+        }
+    };
+    let locked = match deps::load(&lockfile, &packages[members.clone()]) {
+        Ok(locked) => locked,
+        Err(message) => {
+            report(Error::Lockfile { message });
+            return None;
+        }
+    };
+
+    let canonical = |dir: &Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let mut indices = Vec::new();
+    let mut roots = Vec::new();
+    for (i, locked) in locked.iter().enumerate() {
+        let dir = match &locked.dir {
+            Some(dir) => canonical(dir),
+            None => {
+                indices.push(None);
                 continue;
             }
-            Ok(ast) => ast,
         };
+        let found = packages
+            .iter()
+            .position(|package| package.name == locked.name && canonical(&package.dir) == dir);
+        if found.is_some_and(|p| members.contains(&p)) {
+            roots.push(i);
+        }
+        let index = found.or_else(|| {
+            let path = locked.dir.as_ref()?.join("Cargo.toml");
+            let loaded = match package::load(&Files::default(), &path) {
+                Ok(loaded) => loaded,
+                Err(message) => {
+                    report(Error::Manifest { message });
+                    return None;
+                }
+            };
+            // Only the library of a dependency is built for its dependents.
+            let mut package = loaded
+                .into_iter()
+                .find(|package| package.name == locked.name)?;
+            package
+                .targets
+                .retain(|target| target.kind == TargetKind::Lib);
+            packages.push(package);
+            Some(packages.len() - 1)
+        });
+        indices.push(index);
+    }
+    // Roots are listed in the order they were given.
+    roots.sort_by_key(|&i| indices[i]);
+    Some(DependencyTree {
+        locked,
+        packages: indices,
+        roots,
+    })
+}
+
+// Prints a locked package and what it depends on in the style of
+// `cargo tree`, only expanding the dependencies of each package once.
+fn print_dependency(
+    tree: &DependencyTree,
+    i: usize,
+    reports: &[(&Source, StmtVisitor)],
+    prefix: &str,
+    branch: &str,
+    seen: &mut HashSet<usize>,
+) {
+    let locked = &tree.locked[i];
+    let origin = match branch {
+        "" => String::new(),
+        _ => format!(" ({})", locked.origin),
+    };
+    if !seen.insert(i) {
+        println!("{}{}{}{} (*)", prefix, branch, locked, origin);
+        return;
+    }
+    match tree.packages[i] {
+        Some(p) => {
+            let in_package = |source: &Source| source.target.is_some_and(|(i, _)| i == p);
+            let stats = sum_stats(reports.iter().filter(|(source, _)| in_package(source)));
+            println!("{}{}{}{}: {}", prefix, branch, locked, origin, stats);
+        }
+        None => println!(
+            "{}{}{}{}: sources not available locally",
+            prefix, branch, locked, origin
+        ),
+    }
 
-        let mut visitor = StmtVisitor {
-            count: 0,
-            unsafe_count: 0,
-            in_unsafe: 0,
+    let prefix = match branch {
+        "├── " => format!("{}│   ", prefix),
+        "└── " => format!("{}    ", prefix),
+        _ => prefix.to_string(),
+    };
+    let dependencies = &locked.dependencies;
+    for (n, &dependency) in dependencies.iter().enumerate() {
+        let branch = if n + 1 == dependencies.len() {
+            "└── "
+        } else {
+            "├── "
         };
-        visitor.visit_file(&ast);
+        print_dependency(tree, dependency, reports, &prefix, branch, seen);
+    }
+}
 
-        println!("{}: {}/{}", filename, visitor.unsafe_count, visitor.count);
+// Compares the unsafe code of the two versions given to `diff`, which are
+// loaded like any other input.
+fn diff(options: &Options, macros: &Macros) {
+    let versions: Vec<Inputs> = options
+        .inputs
+        .iter()
+        .map(|input| {
+            // A directory holding a package is compared as that package.
+            let manifest = Path::new(input).join("Cargo.toml");
+            let input = if manifest.is_file() {
+                manifest.to_string_lossy().into_owned()
+            } else {
+                input.clone()
+            };
+            let mut inputs = Inputs::load(options, &[input]);
+            inputs.add_targets(options, macros);
+            inputs
+        })
+        .collect();
+    let regions: Vec<Vec<Region>> = versions
+        .iter()
+        .map(|inputs| {
+            let crates = &inputs.crates;
+            let mut regions = Vec::new();
+            for (i, source) in crates.sources.iter().enumerate() {
+                // The items of a target are named after it rather than
+                // `crate`, so that those of a library and a binary differ.
+                let mut module = source.module.clone();
+                if let Some((p, t)) = source.target {
+                    module[0] = inputs.packages[p].targets[t].name.clone();
+                }
+                let cfg = &crates.cfgs[source.krate];
+                regions.extend(diff::regions(i, &source.ast, &module, cfg, macros));
+            }
+            regions
+        })
+        .collect();
 
-        total += visitor.count;
-        unsafe_total += visitor.unsafe_count;
+    let (changes, unchanged) = diff::compare(&regions[0], &regions[1]);
+    let (mut added, mut removed, mut modified) = (0, 0, 0);
+    for change in &changes {
+        // The region, the version it is in, and the old region it replaces.
+        let (level, version, region, previous, message) = match change {
+            Change::Added(region) => {
+                added += 1;
+                (
+                    Level::Added,
+                    1,
+                    region,
+                    None,
+                    "not in the old version".to_string(),
+                )
+            }
+            Change::Removed(region) => {
+                removed += 1;
+                (
+                    Level::Removed,
+                    0,
+                    region,
+                    None,
+                    "not in the new version".to_string(),
+                )
+            }
+            Change::Modified(old, new) => {
+                modified += 1;
+                let source = &versions[0].crates.sources[old.file];
+                let filename = Path::new(&source.filename)
+                    .file_name()
+                    .map(OsStr::to_string_lossy)
+                    .unwrap_or_default();
+                let message = format!(
+                    "changed since {}:{}:{}",
+                    filename,
+                    old.start.line,
+                    old.start.column + 1
+                );
+                (Level::Modified, 1, new, Some(old), message)
+            }
+        };
+        let header = match region.kind {
+            RegionKind::Block => format!("{} in `{}`", region.kind, region.owner),
+            _ => format!("{} `{}`", region.kind, region.owner),
+        };
+        let source = &versions[version].crates.sources[region.file];
+        let filepath = Path::new(&source.filename);
+        let mut diagnostic = Diagnostic::new(level, &header, filepath, &source.src);
+        diagnostic.primary(region.start, region.end, &message);
+        if let Some(old) = previous {
+            let source = &versions[0].crates.sources[old.file];
+            let filepath = Path::new(&source.filename);
+            let mut snippet = Diagnostic::new(level, &header, filepath, &source.src);
+            snippet.secondary(old.start, old.end, "the old version");
+            diagnostic.snippets.push(snippet);
+        }
+        print!("{}", diagnostic);
+    }
+    if !changes.is_empty() {
+        println!();
+    }
+    println!(
+        "unsafe added: {}, removed: {}, modified: {}, unchanged: {}",
+        added, removed, modified, unchanged
+    );
+}
+
+fn setup() -> Result<(Options, Catalog, Macros), String> {
+    let options = Options::parse(env::args().skip(1))?;
+    diagnostic::set_color(options.color);
+    let config = config::load(options.config.as_deref())?;
+    let catalog = Catalog::new(config.as_ref())?;
+    let macros = Macros::new(config.as_ref())?;
+    Ok((options, catalog, macros))
+}
+
+// Reads and parses a file, reporting it on stderr if either fails.
+fn parse(files: &Files, filename: &str) -> Option<(String, syn::File)> {
+    let filepath = PathBuf::from(filename);
+    let src = match files.read(&filepath).map(String::from_utf8) {
+        Ok(Ok(src)) => src,
+        Ok(Err(error)) => {
+            report(Error::Encoding {
+                error: error.utf8_error(),
+                filepath,
+            });
+            return None;
+        }
+        Err(error) => {
+            report(Error::Io { error, filepath });
+            return None;
+        }
+    };
+    let error = match syn::parse_file(&src) {
+        Ok(ast) => return Some((src, ast)),
+        Err(error) => error,
+    };
+    // The items that do parse are analysed without the others. If they all
+    // do, what is wrong is how they are put together, which cannot be left
+    // out.
+    match recover::items(&src) {
+        Some((ast, skipped)) if !skipped.is_empty() => {
+            for skipped in skipped {
+                report(Error::SkippedItem {
+                    skipped,
+                    filepath: filepath.clone(),
+                    source_code: src.clone(),
+                });
+            }
+            Some((src, ast))
+        }
+        _ => {
+            report(Error::ParseFile {
+                error,
+                filepath,
+                source_code: src,
+            });
+            None
+        }
+    }
+}
+
+// Whether any input could not be analysed, which makes the exit status 1.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+// Reports an input that could not be analysed, which the analysis goes on
+// without.
+fn report(err: Error) {
+    FAILED.store(true, Ordering::Relaxed);
+    diagnostic::eprint(err);
+}
+
+// Exits with status 1 if any input failed, once everything else is printed.
+fn exit_status() {
+    if FAILED.load(Ordering::Relaxed) {
+        process::exit(1);
+    }
+}
+
+#[derive(Default)]
+struct Totals {
+    stats: Stats,
+    unsafe_impls: usize,
+    unsafe_traits: usize,
+    ffi_imports: usize,
+    ffi_exports: usize,
+    unused_blocks: usize,
+    unused_fns: usize,
+    cfg_stats: BTreeMap<Option<String>, Stats>,
+    role_stats: BTreeMap<Role, Stats>,
+}
+
+fn sum_stats<'a, I>(reports: I) -> Stats
+where
+    I: Iterator<Item = &'a (&'a Source, StmtVisitor<'a>)>,
+{
+    let mut stats = Stats::default();
+    for (_, visitor) in reports {
+        stats += visitor.stats;
+    }
+    stats
+}
+
+fn print_file(
+    options: &Options,
+    indent: &str,
+    source: &Source,
+    visitor: &StmtVisitor,
+    totals: &mut Totals,
+) {
+    let filename = &source.filename;
+    println!("{}{}: {}", indent, filename, visitor.stats);
+    print_ops(&format!("{}  ", indent), &visitor.stats);
+    for item in &visitor.unsafe_items {
+        println!(
+            "{}  {}:{}:{}: {}",
+            indent,
+            filename,
+            item.start.line,
+            item.start.column + 1,
+            item
+        );
+        if item.is_impl() {
+            totals.unsafe_impls += 1;
+        } else {
+            totals.unsafe_traits += 1;
+        }
+    }
+
+    if options.ffi {
+        for item in &visitor.ffi_items {
+            // Imports are nested under the extern block declaring them.
+            let nesting = if item.is_import() { "    " } else { "  " };
+            println!(
+                "{}{}{}:{}:{}: {}",
+                indent,
+                nesting,
+                filename,
+                item.start.line,
+                item.start.column + 1,
+                item
+            );
+            if item.is_import() {
+                totals.ffi_imports += 1;
+            } else if item.is_export() {
+                totals.ffi_exports += 1;
+            }
+        }
+    }
+
+    if options.unused_unsafe {
+        for unused in &visitor.unused_unsafe {
+            let message = unused.to_string();
+            let filepath = Path::new(filename);
+            let mut diagnostic =
+                Diagnostic::new(Level::Warning, unused.header(), filepath, &source.src);
+            diagnostic.primary(unused.start, unused.end, &message);
+            if let Some((start, end, label)) = unused.outer() {
+                diagnostic.secondary(start, end, label);
+            }
+            print!("{}", diagnostic);
+            if unused.is_fn() {
+                totals.unused_fns += 1;
+            } else {
+                totals.unused_blocks += 1;
+            }
+        }
     }
 
-    println!("total: {}/{}", unsafe_total, total);
+    totals.stats += visitor.stats;
+    for (cfg, stats) in &visitor.cfg_stats {
+        *totals.cfg_stats.entry(cfg.clone()).or_default() += *stats;
+    }
+    for (role, stats) in &visitor.role_stats {
+        *totals.role_stats.entry(*role).or_default() += *stats;
+    }
+}
+
+fn print_ops(indent: &str, stats: &Stats) {
+    if stats.ops.total() == 0 && stats.unresolved_calls == 0 && stats.unexpanded_macros == 0 {
+        return;
+    }
+    print!("{}unsafe operations: {}", indent, stats.ops);
+    if stats.unresolved_calls > 0 {
+        print!("; unresolved calls: {}", stats.unresolved_calls);
+    }
+    if stats.unexpanded_macros > 0 {
+        print!("; unexpanded macros: {}", stats.unexpanded_macros);
+    }
+    println!();
+}
+
+struct Source {
+    filename: String,
+    src: String,
+    ast: syn::File,
+    edition: Edition,
+    module: Vec<String>,
+    // The `cfg` predicates of the `mod` declarations leading to the file.
+    cfgs: Vec<Cfg>,
+    // The role of the file, from its target or its path.
+    role: Role,
+    krate: usize,
+    // The package and target whose module tree the file was found in.
+    target: Option<(usize, usize)>,
+}
+
+// The parsed files and the crates they belong to, keyed by package
+// directory.
+#[derive(Default)]
+struct Crates {
+    sources: Vec<Source>,
+    tables: Vec<SymbolTable>,
+    // The cfg set each crate is analysed under.
+    cfgs: Vec<CfgSet>,
+    indices: HashMap<Option<PathBuf>, usize>,
+    // Macro invocations of each crate, by macro name.
+    invocations: Vec<HashMap<String, usize>>,
+    unsafe_macros: Vec<(String, usize, UnsafeMacro)>,
+}
+
+impl Crates {
+    fn index(
+        &mut self,
+        package_dir: Option<PathBuf>,
+        crate_name: Option<String>,
+        cfg: CfgSet,
+    ) -> usize {
+        let tables = &mut self.tables;
+        let cfgs = &mut self.cfgs;
+        let invocations = &mut self.invocations;
+        *self.indices.entry(package_dir).or_insert_with(|| {
+            tables.push(SymbolTable::new(crate_name));
+            cfgs.push(cfg);
+            invocations.push(HashMap::new());
+            tables.len() - 1
+        })
+    }
+
+    fn add(&mut self, source: Source) {
+        let krate = source.krate;
+        self.tables[krate].add_macros(&source.ast);
+        self.unsafe_macros.extend(
+            unsafe_macros::definitions(&source.ast)
+                .into_iter()
+                .map(|definition| (source.filename.clone(), krate, definition)),
+        );
+        unsafe_macros::count_invocations(&source.ast, &mut self.invocations[krate]);
+        self.sources.push(source);
+    }
 }
 
 fn render_location(
@@ -94,50 +943,53 @@ fn render_location(
     code: &str,
 ) -> fmt::Result {
     let start = err.span().start();
-    let mut end = err.span().end();
+    let end = err.span().end();
 
     if start.line == end.line && start.column == end.column {
         return render_fallback(formatter, err);
     }
+    if code.lines().nth(start.line - 1).is_none() {
+        return render_fallback(formatter, err);
+    }
+
+    let message = err.to_string();
+    let mut diagnostic = Diagnostic::new(Level::Error, "Syn unable to parse file", filepath, code);
+    diagnostic.primary(start, end, &message);
+    let note = "none of the file is analysed";
+    diagnostic.notes.push(note.to_string());
+    diagnostic.fmt(formatter)
+}
 
-    let code_line = match code.lines().nth(start.line - 1) {
-        Some(line) => line,
-        None => return render_fallback(formatter, err),
+// Points at where an item that was left out stopped parsing, and says which
+// lines it spans.
+fn render_skipped(
+    formatter: &mut fmt::Formatter,
+    skipped: &Skipped,
+    filepath: &Path,
+    code: &str,
+) -> fmt::Result {
+    let lines = match skipped.end.line - skipped.start.line {
+        0 => format!("line {}", skipped.start.line),
+        _ => format!("lines {}-{}", skipped.start.line, skipped.end.line),
     };
+    let start = skipped.error.span().start();
+    let end = skipped.error.span().end();
+    if start == end || code.lines().nth(start.line - 1).is_none() {
+        return write!(
+            formatter,
+            "Unable to parse {}, skipping {}: {}",
+            filepath.display(),
+            lines,
+            skipped.error
+        );
+    }
 
-    if end.line > start.line {
-        end.line = start.line;
-        end.column = code_line.len();
-    }
-
-    let filename = filepath
-        .file_name()
-        .map(OsStr::to_string_lossy)
-        .unwrap_or(Cow::Borrowed("main.rs"));
-
-    write!(
-        formatter,
-        "\n\
-         {error}{header}\n\
-         {indent}{arrow} {filename}:{linenum}:{colnum}\n\
-         {indent} {pipe}\n\
-         {label} {pipe} {code}\n\
-         {indent} {pipe} {offset}{underline} {message}\n\
-         ",
-        error = "error".red().bold(),
-        header = ": Syn unable to parse file".bold(),
-        indent = " ".repeat(start.line.to_string().len()),
-        arrow = "-->".blue().bold(),
-        filename = filename,
-        linenum = start.line,
-        colnum = start.column,
-        pipe = "|".blue().bold(),
-        label = start.line.to_string().blue().bold(),
-        code = code_line.trim_end(),
-        offset = " ".repeat(start.column),
-        underline = "^".repeat(end.column - start.column).red().bold(),
-        message = err.to_string().red(),
-    )
+    let header = format!("Syn unable to parse item, skipping {}", lines);
+    let message = skipped.error.to_string();
+    let mut diagnostic = Diagnostic::new(Level::Error, &header, filepath, code);
+    diagnostic.primary(start, end, &message);
+    diagnostic.secondary(skipped.start, skipped.end, "item skipped");
+    diagnostic.fmt(formatter)
 }
 
 fn render_fallback(formatter: &mut fmt::Formatter, err: &syn::Error) -> fmt::Result {
@@ -145,11 +997,36 @@ fn render_fallback(formatter: &mut fmt::Formatter, err: &syn::Error) -> fmt::Res
 }
 
 enum Error {
+    Config {
+        message: String,
+    },
+    Io {
+        error: io::Error,
+        filepath: PathBuf,
+    },
+    Encoding {
+        error: Utf8Error,
+        filepath: PathBuf,
+    },
+    Manifest {
+        message: String,
+    },
+    Lockfile {
+        message: String,
+    },
+    Archive {
+        message: String,
+    },
     ParseFile {
         error: syn::Error,
         filepath: PathBuf,
         source_code: String,
     },
+    SkippedItem {
+        skipped: recover::Skipped,
+        filepath: PathBuf,
+        source_code: String,
+    },
 }
 
 impl Display for Error {
@@ -157,11 +1034,26 @@ impl Display for Error {
         use self::Error::*;
 
         match self {
+            Config { message } => write!(f, "error: {}", message),
+            Io { error, filepath } => {
+                write!(f, "Unable to read {}: {}", filepath.display(), error)
+            }
+            Encoding { error, filepath } => {
+                write!(f, "Unable to decode {}: {}", filepath.display(), error)
+            }
+            Manifest { message } => write!(f, "Unable to load manifest {}", message),
+            Lockfile { message } => write!(f, "Unable to load lockfile {}", message),
+            Archive { message } => write!(f, "Unable to read archive {}", message),
             ParseFile {
                 error,
                 filepath,
                 source_code,
             } => render_location(f, error, filepath, source_code),
+            SkippedItem {
+                skipped,
+                filepath,
+                source_code,
+            } => render_skipped(f, skipped, filepath, source_code),
         }
     }
 }
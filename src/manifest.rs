@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config;
+use crate::role::Role;
+use crate::toml;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edition {
+    E2015,
+    E2018,
+    E2021,
+    E2024,
+}
+
+impl FromStr for Edition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Edition, String> {
+        match s {
+            "2015" => Ok(Edition::E2015),
+            "2018" => Ok(Edition::E2018),
+            "2021" => Ok(Edition::E2021),
+            "2024" => Ok(Edition::E2024),
+            _ => Err(format!("unknown edition `{}`", s)),
+        }
+    }
+}
+
+impl Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let year = match self {
+            Edition::E2015 => "2015",
+            Edition::E2018 => "2018",
+            Edition::E2021 => "2021",
+            Edition::E2024 => "2024",
+        };
+        f.write_str(year)
+    }
+}
+
+pub fn read(path: &Path) -> Option<toml::Table> {
+    let src = fs::read_to_string(path).ok()?;
+    toml::parse(&src).ok()
+}
+
+// Returns the closest `Cargo.toml` containing a `[package]` above `path`.
+pub fn find_package(path: &Path) -> Option<(PathBuf, toml::Table)> {
+    let path = fs::canonicalize(path).ok()?;
+    for dir in path.ancestors().skip(1) {
+        let candidate = dir.join("Cargo.toml");
+        if let Some(manifest) = read(&candidate) {
+            if manifest.contains_key("package") {
+                return Some((candidate, manifest));
+            }
+        }
+    }
+    None
+}
+
+// Returns the closest `Cargo.toml` containing a `[workspace]` that is an
+// ancestor of (or equal to) the manifest at `path`.
+pub fn find_workspace(path: &Path) -> Option<(PathBuf, toml::Table)> {
+    for dir in path.ancestors().skip(1) {
+        let candidate = dir.join("Cargo.toml");
+        if let Some(manifest) = read(&candidate) {
+            if manifest.contains_key("workspace") {
+                return Some((candidate, manifest));
+            }
+        }
+    }
+    None
+}
+
+// Looks up a `[package]` key, following `key.workspace = true` to the
+// `[workspace.package]` table of the enclosing workspace.
+pub fn package_field(path: &Path, manifest: &toml::Table, key: &str) -> Option<toml::Value> {
+    let value = manifest.get("package")?.get(key)?;
+    let inherited = value.get("workspace").and_then(toml::Value::as_bool);
+    if inherited != Some(true) {
+        return Some(value.clone());
+    }
+    let (_, workspace) = find_workspace(path)?;
+    workspace
+        .get("workspace")?
+        .get("package")?
+        .get(key)
+        .cloned()
+}
+
+pub fn edition(path: &Path, manifest: &toml::Table) -> Edition {
+    // Cargo defaults to the 2015 edition when none is given.
+    package_field(path, manifest, "edition")
+        .and_then(|value| value.as_str()?.parse().ok())
+        .unwrap_or(Edition::E2015)
+}
+
+// The `[features]` table, mapping each feature to what it enables.
+pub fn features(manifest: &toml::Table) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut features = HashMap::new();
+    if let Some(table) = manifest.get("features").and_then(toml::Value::as_table) {
+        for feature in table.keys() {
+            let enables = config::strings(table, "features", feature)?;
+            features.insert(feature.clone(), enables);
+        }
+    }
+    Ok(features)
+}
+
+// Where a source file sits within its Cargo package.
+pub struct Location {
+    pub package_dir: PathBuf,
+    pub crate_name: String,
+    pub edition: Edition,
+    pub module: Vec<String>,
+    pub features: HashMap<String, Vec<String>>,
+    pub role: Role,
+}
+
+pub fn locate(file: &Path) -> Option<Location> {
+    let (path, manifest) = find_package(file)?;
+    let package_dir = path.parent()?.to_path_buf();
+    let relative = fs::canonicalize(file).ok()?;
+    let relative = relative.strip_prefix(&package_dir).ok()?;
+    Some(Location {
+        crate_name: crate_name(&manifest)?,
+        edition: edition(&path, &manifest),
+        module: module_path(relative),
+        role: Role::for_path(relative, true),
+        features: features(&manifest).unwrap_or_default(),
+        package_dir,
+    })
+}
+
+// The name the library target is imported as by the package's other targets.
+pub fn crate_name(manifest: &toml::Table) -> Option<String> {
+    let lib_name = manifest.get("lib").and_then(|lib| lib.get("name"));
+    let name = lib_name.or_else(|| manifest.get("package")?.get("name"))?;
+    Some(name.as_str()?.replace('-', "_"))
+}
+
+// Guesses the module path of a file from its path relative to the package
+// root, following Cargo's conventional target layout.
+pub fn module_path(relative: &Path) -> Vec<String> {
+    let mut dirs: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let file = dirs.pop().unwrap_or_default();
+    let stem = file.strip_suffix(".rs").unwrap_or(&file);
+
+    // The directory holding the target's root file, e.g. `src` or
+    // `src/bin/tool`, and whether that target is a single file.
+    let (target_dir, single_file) = match dirs.first().map(String::as_str) {
+        Some("src") if dirs.get(1).map(String::as_str) == Some("bin") => match dirs.len() {
+            2 => (2, true),
+            _ => (3, false),
+        },
+        Some("src") => (1, false),
+        Some("tests" | "examples" | "benches") => match dirs.len() {
+            1 => (1, true),
+            _ => (2, false),
+        },
+        Some(_) => (0, false),
+        None => (0, stem == "build"),
+    };
+
+    let mut module = vec!["crate".to_string()];
+    let rest = &dirs[target_dir.min(dirs.len())..];
+    if rest.is_empty() && (single_file || stem == "lib" || stem == "main") {
+        return module;
+    }
+    module.extend(rest.iter().cloned());
+    if stem != "mod" {
+        module.push(stem.to_string());
+    }
+    module
+}
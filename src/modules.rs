@@ -0,0 +1,184 @@
+// Follows the `mod foo;` declarations of a crate from its root to find the
+// files that are compiled as part of it, along with their module paths.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use syn::{Expr, ExprLit, Item, ItemMacro, ItemMod, Lit, Meta};
+
+use crate::cfg::{Cfg, CfgSet};
+use crate::files::Files;
+use crate::macro_rules::{self, MacroRules};
+use crate::macros::{MacroBody, Macros};
+
+pub struct ModuleFile {
+    pub path: PathBuf,
+    pub module: Vec<String>,
+    // The `cfg` predicates of the `mod` declarations leading to the file.
+    pub cfgs: Vec<Cfg>,
+    // Whether the submodules of this file are looked up next to it, as for
+    // crate roots, `mod.rs` files and files loaded through `#[path]`, rather
+    // than in a directory named after it.
+    owns_dir: bool,
+}
+
+impl ModuleFile {
+    pub fn root(path: PathBuf) -> ModuleFile {
+        ModuleFile {
+            path,
+            module: vec!["crate".to_string()],
+            cfgs: Vec::new(),
+            owns_dir: true,
+        }
+    }
+
+    // The files of the modules declared without a body in `file`, skipping
+    // those `cfg` rules out. Declarations in the input of known macros, such
+    // as `cfg_if!`, and in the expansions of the `macro_rules!` macros seen so
+    // far are followed too. A module whose file cannot be found gets the
+    // first path rustc would try.
+    pub fn submodules(
+        &self,
+        files: &Files,
+        file: &syn::File,
+        cfg: &CfgSet,
+        macros: &Macros,
+        rules: &mut HashMap<String, MacroRules>,
+    ) -> Vec<ModuleFile> {
+        let parent = self.path.parent().unwrap_or(Path::new(""));
+        let dir = match self.path.file_stem() {
+            Some(stem) if !self.owns_dir => parent.join(stem),
+            _ => parent.to_path_buf(),
+        };
+        let mut collector = Collector {
+            cfg,
+            macros,
+            rules,
+            depth: 0,
+            submodules: Vec::new(),
+        };
+        collector.collect(&file.items, &dir, parent, &self.module, &self.cfgs);
+        let mut submodules = collector.submodules;
+        // `foo.rs` is preferred over `foo/mod.rs` when both exist.
+        for submodule in &mut submodules {
+            let mod_rs = submodule.path.with_extension("").join("mod.rs");
+            if !submodule.owns_dir && !files.is_file(&submodule.path) && files.is_file(&mod_rs) {
+                submodule.path = mod_rs;
+                submodule.owns_dir = true;
+            }
+        }
+        submodules
+    }
+}
+
+struct Collector<'a> {
+    cfg: &'a CfgSet,
+    macros: &'a Macros,
+    rules: &'a mut HashMap<String, MacroRules>,
+    // The number of macro expansions the items are nested in.
+    depth: usize,
+    submodules: Vec<ModuleFile>,
+}
+
+impl Collector<'_> {
+    // `path_dir` is where `#[path]` attributes are resolved from, which is
+    // the file's own directory outside of inline modules.
+    fn collect(
+        &mut self,
+        items: &[Item],
+        dir: &Path,
+        path_dir: &Path,
+        module: &[String],
+        cfgs: &[Cfg],
+    ) {
+        for item in items {
+            let item = match item {
+                Item::Mod(item) if self.cfg.is_active(&item.attrs) => item,
+                Item::Macro(item) if self.cfg.is_active(&item.attrs) => {
+                    let expanded = self.expand(item);
+                    let mut cfgs = cfgs.to_vec();
+                    cfgs.extend(self.cfg.predicates(&item.attrs));
+                    self.depth += 1;
+                    self.collect(&expanded, dir, path_dir, module, &cfgs);
+                    self.depth -= 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            let mut child = module.to_vec();
+            child.push(item.ident.to_string());
+            let mut child_cfgs = cfgs.to_vec();
+            child_cfgs.extend(self.cfg.predicates(&item.attrs));
+            let attr_path = path_attr(item, self.cfg);
+
+            if let Some((_, items)) = &item.content {
+                let dir = match &attr_path {
+                    Some(path) => dir.join(path),
+                    None => dir.join(item.ident.to_string()),
+                };
+                self.collect(items, &dir, &dir, &child, &child_cfgs);
+                continue;
+            }
+
+            let submodule = match attr_path {
+                Some(path) => ModuleFile {
+                    path: path_dir.join(path),
+                    module: child,
+                    cfgs: child_cfgs,
+                    owns_dir: true,
+                },
+                None => ModuleFile {
+                    path: dir.join(format!("{}.rs", item.ident)),
+                    module: child,
+                    cfgs: child_cfgs,
+                    owns_dir: false,
+                },
+            };
+            self.submodules.push(submodule);
+        }
+    }
+
+    // Records a `macro_rules!` definition, or returns the items an
+    // invocation expands to.
+    fn expand(&mut self, item: &ItemMacro) -> Vec<Item> {
+        if let Some(name) = &item.ident {
+            if item.mac.path.is_ident("macro_rules") {
+                if let Some(rules) = MacroRules::parse(&item.mac.tokens) {
+                    self.rules.insert(name.to_string(), rules);
+                }
+            }
+            return Vec::new();
+        }
+        if self.depth >= macro_rules::MAX_DEPTH {
+            return Vec::new();
+        }
+        let local = item.mac.path.get_ident().and_then(|name| {
+            let rules = self.rules.get(&name.to_string())?;
+            rules.expand(&item.mac.tokens, name.span())
+        });
+        if let Some(tokens) = local {
+            return macro_rules::parse_items(tokens).unwrap_or_default();
+        }
+        match self.macros.parse(&item.mac) {
+            Some(MacroBody::Items(items)) => items,
+            _ => Vec::new(),
+        }
+    }
+}
+
+// The `#[path]` of a module, which may be given through `cfg_attr`.
+fn path_attr(item: &ItemMod, cfg: &CfgSet) -> Option<String> {
+    let mut path = None;
+    cfg.each_attr(&item.attrs, |meta| match meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") && path.is_none() => {
+            if let Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) = &meta.value
+            {
+                path = Some(lit.value());
+            }
+        }
+        _ => {}
+    });
+    path
+}
@@ -0,0 +1,122 @@
+use std::fs;
+
+use crate::cfg::CfgSet;
+use crate::diagnostic::ColorChoice;
+use crate::glob::Glob;
+use crate::manifest::Edition;
+use crate::walk::Filters;
+
+pub struct Options {
+    pub config: Option<String>,
+    pub edition: Option<Edition>,
+    pub ffi: bool,
+    pub unused_unsafe: bool,
+    pub filters: Filters,
+    pub cfg: CfgSet,
+    pub default_features: bool,
+    pub by_cfg: bool,
+    pub by_function: bool,
+    pub production_only: bool,
+    pub deps: bool,
+    // Whether to compare two versions of a crate, given as the two inputs,
+    // rather than analyse the inputs.
+    pub diff: bool,
+    // The name to report the source read from stdin, given as `-`, under.
+    pub stdin_filename: Option<String>,
+    pub color: ColorChoice,
+    pub inputs: Vec<String>,
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Options, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options {
+            config: None,
+            edition: None,
+            ffi: false,
+            unused_unsafe: false,
+            filters: Filters::default(),
+            cfg: CfgSet::default(),
+            default_features: true,
+            by_cfg: false,
+            by_function: false,
+            production_only: false,
+            deps: false,
+            diff: false,
+            stdin_filename: None,
+            color: ColorChoice::Auto,
+            inputs: Vec::new(),
+        };
+
+        let mut args = args.into_iter().peekable();
+        if args.peek().map(String::as_str) == Some("diff") {
+            args.next();
+            options.diff = true;
+        }
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                options.inputs.extend(args.by_ref());
+                break;
+            }
+            // `@file` stands for the paths listed in `file`, one per line,
+            // for lists too long for the command line.
+            if let Some(list) = arg.strip_prefix('@') {
+                let paths = fs::read_to_string(list)
+                    .map_err(|err| format!("unable to read `{}`: {}", list, err))?;
+                let paths = paths.lines().map(str::trim).filter(|path| !path.is_empty());
+                options.inputs.extend(paths.map(str::to_string));
+                continue;
+            }
+            if !arg.starts_with("--") {
+                options.inputs.push(arg);
+                continue;
+            }
+
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value for `{}`", flag))
+            };
+            match flag.as_str() {
+                "--config" => options.config = Some(value()?),
+                "--ffi" => options.ffi = true,
+                "--unused-unsafe" => options.unused_unsafe = true,
+                "--edition" => options.edition = Some(value()?.parse()?),
+                "--exclude" => options.filters.exclude.push(Glob::new(&value()?)?),
+                "--include" => options.filters.include.push(Glob::new(&value()?)?),
+                "--features" => {
+                    let features = value()?;
+                    let features = features.split([',', ' ']).filter(|f| !f.is_empty());
+                    features.for_each(|feature| options.cfg.add_feature(feature));
+                }
+                "--all-features" => options.cfg.enable_all_features(),
+                "--no-default-features" => {
+                    options.default_features = false;
+                    options.cfg.enable();
+                }
+                "--cfg" => options.cfg.add_cfg(&value()?)?,
+                "--target-os" => options.cfg.set_target_os(&value()?),
+                "--target-arch" => options.cfg.set_target_arch(&value()?),
+                "--by-cfg" => options.by_cfg = true,
+                "--by-function" => options.by_function = true,
+                "--production-only" => options.production_only = true,
+                "--deps" => options.deps = true,
+                "--stdin-filename" => options.stdin_filename = Some(value()?),
+                "--color" => options.color = value()?.parse()?,
+                _ => return Err(format!("unknown option `{}`", flag)),
+            }
+        }
+
+        if options.diff && options.inputs.len() != 2 {
+            return Err("`diff` takes the old and the new version to compare".to_string());
+        }
+        Ok(options)
+    }
+}
@@ -0,0 +1,302 @@
+// The packages of a `Cargo.toml` and the targets they build, discovered the
+// way Cargo does: from the `[lib]`, `[[bin]]`, `[[example]]`, `[[test]]` and
+// `[[bench]]` tables, the conventional layout and the build script.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+use crate::files::Files;
+use crate::glob::Glob;
+use crate::manifest::{self, Edition};
+use crate::toml;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TargetKind {
+    Lib,
+    Bin,
+    Example,
+    Test,
+    Bench,
+    BuildScript,
+}
+
+impl Display for TargetKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            TargetKind::Lib => "lib",
+            TargetKind::Bin => "bin",
+            TargetKind::Example => "example",
+            TargetKind::Test => "test",
+            TargetKind::Bench => "bench",
+            TargetKind::BuildScript => "build script",
+        };
+        f.write_str(kind)
+    }
+}
+
+pub struct Target {
+    pub kind: TargetKind,
+    pub name: String,
+    // The crate root, under the package directory as given.
+    pub root: PathBuf,
+    pub edition: Edition,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} `{}`", self.kind, self.name)
+    }
+}
+
+pub struct Package {
+    pub name: String,
+    // The name the library target is imported as.
+    pub crate_name: String,
+    pub dir: PathBuf,
+    pub targets: Vec<Target>,
+    // The `[features]` table, mapping each feature to what it enables.
+    pub features: HashMap<String, Vec<String>>,
+}
+
+// Loads the package of a manifest, or the members of a workspace manifest
+// along with its root package if it has one.
+pub fn load(files: &Files, path: &Path) -> Result<Vec<Package>, String> {
+    let error = |message: String| format!("{}: {}", path.display(), message);
+    let src = files
+        .read_to_string(path)
+        .map_err(|err| error(err.to_string()))?;
+    let manifest = toml::parse(&src).map_err(|err| error(err.to_string()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut packages = Vec::new();
+    if manifest.contains_key("package") {
+        packages.push(package(files, path, &manifest).map_err(error)?);
+    }
+    let workspace = match manifest.get("workspace") {
+        Some(workspace) => workspace,
+        None if packages.is_empty() => {
+            return Err(error("no `[package]` or `[workspace]` table".to_string()))
+        }
+        None => return Ok(packages),
+    };
+
+    let members = match workspace.as_table() {
+        Some(workspace) => {
+            let members = strings(workspace, "members").map_err(error)?;
+            let exclude = strings(workspace, "exclude").map_err(error)?;
+            member_dirs(files, dir, &members, &exclude).map_err(error)?
+        }
+        None => Vec::new(),
+    };
+    for member in members {
+        // A workspace may list its own root package as a member.
+        if member.components().eq(dir.components()) {
+            continue;
+        }
+        let path = member.join("Cargo.toml");
+        let error = |message: String| format!("{}: {}", path.display(), message);
+        let src = files
+            .read_to_string(&path)
+            .map_err(|err| error(err.to_string()))?;
+        let manifest = toml::parse(&src).map_err(|err| error(err.to_string()))?;
+        packages.push(package(files, &path, &manifest).map_err(error)?);
+    }
+    Ok(packages)
+}
+
+fn strings(table: &toml::Table, key: &str) -> Result<Vec<String>, String> {
+    crate::config::strings(table, "workspace", key)
+}
+
+// Expands the `members` of a workspace, which may contain globs, into
+// member directories in the order they are listed.
+fn member_dirs(
+    files: &Files,
+    dir: &Path,
+    members: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, String> {
+    let mut dirs = Vec::new();
+    for member in members {
+        let mut matches = vec![dir.to_path_buf()];
+        for segment in member.split('/').filter(|segment| !segment.is_empty()) {
+            if !segment.contains(['*', '?', '[']) {
+                matches = matches.into_iter().map(|m| m.join(segment)).collect();
+                continue;
+            }
+            let glob = Glob::new(segment)?;
+            let mut expanded = Vec::new();
+            for parent in matches {
+                let entries = match files.read_dir(&parent) {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
+                let mut names: Vec<String> = entries
+                    .into_iter()
+                    .filter(|entry| files.is_dir(entry))
+                    .filter_map(|entry| Some(entry.file_name()?.to_string_lossy().into_owned()))
+                    .filter(|name| glob.matches(name, true))
+                    .collect();
+                names.sort();
+                expanded.extend(names.into_iter().map(|name| parent.join(name)));
+            }
+            matches = expanded;
+        }
+        // Globs only match directories that are packages.
+        if member.contains(['*', '?', '[']) {
+            matches.retain(|m| files.is_file(&m.join("Cargo.toml")));
+        }
+        dirs.extend(matches);
+    }
+    let excluded: Vec<PathBuf> = exclude.iter().map(|path| dir.join(path)).collect();
+    dirs.retain(|member| !excluded.iter().any(|path| member.starts_with(path)));
+    Ok(dirs)
+}
+
+fn package(files: &Files, path: &Path, manifest: &toml::Table) -> Result<Package, String> {
+    let name = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .ok_or("missing `package.name`")?
+        .to_string();
+    let crate_name = manifest::crate_name(manifest).unwrap_or_else(|| name.replace('-', "_"));
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    // Inherited fields are looked up in the manifests above the package.
+    let canonical = files.canonicalize(path);
+    let edition = manifest::edition(&canonical, manifest);
+    let package = manifest.get("package").unwrap();
+    let auto = |key: &str| package.get(key).and_then(toml::Value::as_bool) != Some(false);
+
+    let mut targets = Vec::new();
+    let lib = manifest.get("lib");
+    let lib_path = lib
+        .and_then(|lib| lib.get("path"))
+        .and_then(toml::Value::as_str);
+    let lib_root = match lib_path {
+        Some(lib_path) => Some(dir.join(lib_path)),
+        None => Some(dir.join("src/lib.rs")).filter(|root| files.is_file(root)),
+    };
+    if let Some(root) = lib_root {
+        targets.push(Target {
+            kind: TargetKind::Lib,
+            name: crate_name.clone(),
+            root,
+            edition: target_edition(lib, edition),
+        });
+    }
+
+    let kinds = [
+        (TargetKind::Bin, "bin", "src/bin", "autobins"),
+        (TargetKind::Example, "example", "examples", "autoexamples"),
+        (TargetKind::Test, "test", "tests", "autotests"),
+        (TargetKind::Bench, "bench", "benches", "autobenches"),
+    ];
+    for (kind, key, target_dir, auto_key) in kinds {
+        let mut discovered = Vec::new();
+        if auto(auto_key) {
+            if kind == TargetKind::Bin && files.is_file(&dir.join("src/main.rs")) {
+                discovered.push((name.clone(), dir.join("src/main.rs")));
+            }
+            discovered.extend(discover(files, &dir.join(target_dir)));
+        }
+
+        let declared = manifest.get(key).and_then(toml::Value::as_array);
+        for table in declared.unwrap_or_default() {
+            let target_name = table
+                .get("name")
+                .and_then(toml::Value::as_str)
+                .ok_or_else(|| format!("`[[{}]]` without a `name`", key))?;
+            let root = match table.get("path").and_then(toml::Value::as_str) {
+                Some(root) => dir.join(root),
+                None => default_root(files, &dir, kind, target_dir, target_name, &name),
+            };
+            discovered.retain(|(name, path)| name != target_name && *path != root);
+            targets.push(Target {
+                kind,
+                name: target_name.to_string(),
+                root,
+                edition: target_edition(Some(table), edition),
+            });
+        }
+        targets.extend(discovered.into_iter().map(|(name, root)| Target {
+            kind,
+            name,
+            root,
+            edition,
+        }));
+    }
+
+    let build = match package.get("build") {
+        Some(toml::Value::String(build)) => Some(dir.join(build)),
+        Some(toml::Value::Boolean(false)) => None,
+        _ => Some(dir.join("build.rs")).filter(|root| files.is_file(root)),
+    };
+    if let Some(root) = build {
+        targets.push(Target {
+            kind: TargetKind::BuildScript,
+            name: "build-script-build".to_string(),
+            root,
+            edition,
+        });
+    }
+
+    // Targets of the same kind are listed by name, like `cargo` does.
+    targets.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+    Ok(Package {
+        name,
+        crate_name,
+        dir,
+        targets,
+        features: manifest::features(manifest)?,
+    })
+}
+
+fn target_edition(table: Option<&toml::Value>, package: Edition) -> Edition {
+    table
+        .and_then(|table| table.get("edition"))
+        .and_then(|edition| edition.as_str()?.parse().ok())
+        .unwrap_or(package)
+}
+
+// Single file targets `dir/name.rs` and multi-file targets `dir/name/main.rs`.
+fn discover(files: &Files, dir: &Path) -> Vec<(String, PathBuf)> {
+    let entries = match files.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut targets: Vec<(String, PathBuf)> = entries
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            if files.is_dir(&path) {
+                let main = path.join("main.rs");
+                return files.is_file(&main).then_some((name, main));
+            }
+            let stem = name.strip_suffix(".rs")?;
+            Some((stem.to_string(), path))
+        })
+        .collect();
+    targets.sort();
+    targets
+}
+
+fn default_root(
+    files: &Files,
+    dir: &Path,
+    kind: TargetKind,
+    target_dir: &str,
+    name: &str,
+    package: &str,
+) -> PathBuf {
+    if kind == TargetKind::Bin && name == package && files.is_file(&dir.join("src/main.rs")) {
+        return dir.join("src/main.rs");
+    }
+    let file = dir.join(target_dir).join(format!("{}.rs", name));
+    let main = dir.join(target_dir).join(name).join("main.rs");
+    if !files.is_file(&file) && files.is_file(&main) {
+        return main;
+    }
+    file
+}
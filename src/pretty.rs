@@ -0,0 +1,105 @@
+// Renders token streams roughly the way rustfmt would lay out types, paths
+// and signatures, e.g. `impl<T: Send> Send for Foo<T>` rather than the
+// `impl < T : Send > Send for Foo < T >` produced by `TokenStream::to_string`.
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::ItemImpl;
+
+enum Token {
+    Word(String),
+    Punct(String),
+    Open(char),
+    Close(char),
+}
+
+pub fn tokens<T: ToTokens>(node: &T) -> String {
+    let mut flat = Vec::new();
+    flatten(node.to_token_stream(), &mut flat);
+
+    let mut out = String::new();
+    let mut prev: Option<&Token> = None;
+    for token in &flat {
+        if let Some(prev) = prev {
+            if space_between(prev, token) {
+                out.push(' ');
+            }
+        }
+        match token {
+            Token::Word(s) | Token::Punct(s) => out.push_str(s),
+            Token::Open(c) | Token::Close(c) => out.push(*c),
+        }
+        prev = Some(token);
+    }
+    out
+}
+
+// Names an impl the way rustc does in paths, `Type` for inherent impls and
+// `<Type as Trait>` for trait impls.
+pub fn impl_name(node: &ItemImpl) -> String {
+    let self_ty = tokens(&node.self_ty);
+    match &node.trait_ {
+        Some((bang, path, _)) => {
+            let bang = if bang.is_some() { "!" } else { "" };
+            format!("<{} as {}{}>", self_ty, bang, tokens(path))
+        }
+        None => self_ty,
+    }
+}
+
+fn flatten(stream: TokenStream, out: &mut Vec<Token>) {
+    let mut joint = false;
+    for tree in stream {
+        match tree {
+            TokenTree::Ident(ident) => match out.last_mut() {
+                Some(Token::Punct(p)) if joint && p == "'" => {
+                    *out.last_mut().unwrap() = Token::Word(format!("'{}", ident));
+                }
+                _ => out.push(Token::Word(ident.to_string())),
+            },
+            TokenTree::Literal(lit) => out.push(Token::Word(lit.to_string())),
+            TokenTree::Punct(punct) => {
+                match out.last_mut() {
+                    Some(Token::Punct(p)) if joint => p.push(punct.as_char()),
+                    _ => out.push(Token::Punct(punct.as_char().to_string())),
+                }
+                joint = punct.spacing() == Spacing::Joint;
+                continue;
+            }
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ('(', ')'),
+                    Delimiter::Bracket => ('[', ']'),
+                    Delimiter::Brace => ('{', '}'),
+                    Delimiter::None => {
+                        flatten(group.stream(), out);
+                        joint = false;
+                        continue;
+                    }
+                };
+                out.push(Token::Open(open));
+                flatten(group.stream(), out);
+                out.push(Token::Close(close));
+            }
+        }
+        joint = false;
+    }
+}
+
+fn space_between(prev: &Token, next: &Token) -> bool {
+    match (prev, next) {
+        (Token::Open('{'), _) | (_, Token::Close('}')) => true,
+        (Token::Open(_), _) | (_, Token::Close(_)) => false,
+        (Token::Punct(p), Token::Punct(q)) if q == "?" => matches!(p.as_str(), ":" | "+" | ","),
+        (_, Token::Punct(p)) if matches!(p.as_str(), "," | ";" | ":" | "::" | "." | "?") => false,
+        (Token::Punct(p), _)
+            if matches!(p.as_str(), "::" | "." | "&" | "*" | "#" | "!" | "<" | "?") =>
+        {
+            false
+        }
+        (Token::Word(_) | Token::Close(_), Token::Open('(' | '[')) => false,
+        (Token::Word(_), Token::Punct(p)) if p == "<" => false,
+        (_, Token::Punct(p)) if p.starts_with('>') => false,
+        _ => true,
+    }
+}
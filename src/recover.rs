@@ -0,0 +1,126 @@
+// Recovers what it can of a file that syn cannot parse, such as one using
+// syntax that only nightly compilers accept. The tokens of the file are split
+// into top-level items by their braces and semicolons, and each item is
+// parsed on its own, so that one item that does not parse does not hide the
+// unsafe code of the others.
+
+use proc_macro2::{Delimiter, LineColumn, TokenStream, TokenTree};
+use syn::parse::Parser;
+use syn::{Attribute, Item};
+
+// An item that was left out of the file.
+pub struct Skipped {
+    pub start: LineColumn,
+    pub end: LineColumn,
+    pub error: syn::Error,
+}
+
+// Keywords that begin an item, and so end an item that ends in a block.
+const ITEM_KEYWORDS: &[&str] = &[
+    "async",
+    "auto",
+    "const",
+    "default",
+    "enum",
+    "extern",
+    "fn",
+    "impl",
+    "macro_rules",
+    "mod",
+    "pub",
+    "safe",
+    "static",
+    "struct",
+    "trait",
+    "type",
+    "union",
+    "unsafe",
+    "use",
+];
+
+// Parses the items of `src` one at a time, returning the file made of those
+// that parse and the spans of those that do not. Returns `None` if the file
+// cannot even be split into tokens.
+pub fn items(src: &str) -> Option<(syn::File, Vec<Skipped>)> {
+    // A shebang is not a token, but its line still counts.
+    let src = match src.strip_prefix("#!") {
+        Some(rest) if !rest.trim_start().starts_with('[') => {
+            &src[src.find('\n').unwrap_or(src.len())..]
+        }
+        _ => src,
+    };
+    let tokens: Vec<TokenTree> = src.parse::<TokenStream>().ok()?.into_iter().collect();
+
+    let mut file = syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items: Vec::new(),
+    };
+    let mut skipped = Vec::new();
+    for chunk in split(&tokens) {
+        let start = chunk[0].span().start();
+        let end = chunk[chunk.len() - 1].span().end();
+        let stream: TokenStream = chunk.into_iter().collect();
+        let parsed = if is_inner_attribute(&stream) {
+            Attribute::parse_inner
+                .parse2(stream)
+                .map(|attrs| file.attrs.extend(attrs))
+        } else {
+            syn::parse2::<Item>(stream).map(|item| file.items.push(item))
+        };
+        if let Err(error) = parsed {
+            skipped.push(Skipped { start, end, error });
+        }
+    }
+    Some((file, skipped))
+}
+
+// Splits the tokens of a file after each semicolon, inner attribute and
+// block that is followed by the start of another item.
+fn split(tokens: &[TokenTree]) -> Vec<Vec<TokenTree>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let ends = match token {
+            TokenTree::Punct(punct) => punct.as_char() == ';',
+            TokenTree::Group(group) => match group.delimiter() {
+                Delimiter::Brace => i + 1 == tokens.len() || starts_item(&tokens[i + 1..]),
+                Delimiter::Bracket => matches!(
+                    chunk.as_slice(),
+                    [TokenTree::Punct(hash), TokenTree::Punct(bang)]
+                        if hash.as_char() == '#' && bang.as_char() == '!'
+                ),
+                _ => false,
+            },
+            _ => false,
+        };
+        chunk.push(token.clone());
+        if ends {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+// Whether the tokens begin with an attribute, an item keyword or a macro
+// invocation such as `thread_local! { ... }`.
+fn starts_item(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [TokenTree::Punct(punct), ..] => punct.as_char() == '#',
+        [TokenTree::Ident(_), TokenTree::Punct(bang), ..] if bang.as_char() == '!' => true,
+        [TokenTree::Ident(ident), ..] => ITEM_KEYWORDS.contains(&ident.to_string().as_str()),
+        _ => false,
+    }
+}
+
+fn is_inner_attribute(stream: &TokenStream) -> bool {
+    let mut tokens = stream.clone().into_iter();
+    matches!(
+        (tokens.next(), tokens.next()),
+        (Some(TokenTree::Punct(hash)), Some(TokenTree::Punct(bang)))
+            if hash.as_char() == '#' && bang.as_char() == '!'
+    )
+}
@@ -0,0 +1,104 @@
+use std::fmt::{self, Display};
+use std::path::{Component, Path};
+
+use syn::Attribute;
+
+use crate::cfg::Cfg;
+use crate::package::TargetKind;
+
+// What a piece of code is compiled for, so that tests and examples can be
+// accounted apart from the code that ships.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    #[default]
+    Production,
+    Test,
+    Bench,
+    Example,
+    BuildScript,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let role = match self {
+            Role::Production => "production",
+            Role::Test => "test",
+            Role::Bench => "bench",
+            Role::Example => "example",
+            Role::BuildScript => "build script",
+        };
+        f.write_str(role)
+    }
+}
+
+impl Role {
+    pub fn for_target(kind: TargetKind) -> Role {
+        match kind {
+            TargetKind::Lib | TargetKind::Bin => Role::Production,
+            TargetKind::Test => Role::Test,
+            TargetKind::Bench => Role::Bench,
+            TargetKind::Example => Role::Example,
+            TargetKind::BuildScript => Role::BuildScript,
+        }
+    }
+
+    // Guesses the role of a file from its path relative to its package, or
+    // from any of its directories for files outside of a package.
+    pub fn for_path(path: &Path, in_package: bool) -> Role {
+        let dirs: Vec<&str> = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .filter_map(|c| match c {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        if in_package && dirs.is_empty() && path.file_name() == Some("build.rs".as_ref()) {
+            return Role::BuildScript;
+        }
+        let dirs = if in_package {
+            &dirs[..dirs.len().min(1)]
+        } else {
+            &dirs[..]
+        };
+        for dir in dirs {
+            match *dir {
+                "tests" => return Role::Test,
+                "benches" => return Role::Bench,
+                "examples" => return Role::Example,
+                _ => {}
+            }
+        }
+        Role::Production
+    }
+
+    // The role of an item within code of role `self`: `#[test]` and
+    // `#[bench]` functions, and anything behind `#[cfg(test)]`.
+    pub fn nested(self, attrs: &[Attribute], cfgs: &[Cfg]) -> Role {
+        if self != Role::Production {
+            return self;
+        }
+        for attr in attrs {
+            match attr.path().segments.last() {
+                Some(segment) if segment.ident == "test" => return Role::Test,
+                Some(segment) if segment.ident == "bench" => return Role::Bench,
+                _ => {}
+            }
+        }
+        if cfgs.iter().any(requires_test) {
+            return Role::Test;
+        }
+        Role::Production
+    }
+}
+
+// Whether a predicate can only hold when compiling tests.
+fn requires_test(cfg: &Cfg) -> bool {
+    match cfg {
+        Cfg::Name(name) => name == "test",
+        Cfg::All(cfgs) => cfgs.iter().any(requires_test),
+        Cfg::Any(cfgs) => !cfgs.is_empty() && cfgs.iter().all(requires_test),
+        Cfg::KeyValue(..) | Cfg::Not(_) => false,
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use syn::visit::{self, Visit};
+use syn::{
+    FnArg, ForeignItem, ForeignItemFn, ForeignItemStatic, ImplItem, ImplItemFn, ImplItemMacro,
+    Item, ItemFn, ItemImpl, ItemMacro, ItemMod, ItemStatic, ItemStruct, ItemTrait, ItemUnion,
+    ItemUse, Macro, Path, Signature, StaticMutability, TraitItem, TraitItemFn, Type, UseTree,
+};
+
+use crate::cfg::{self, CfgSet};
+use crate::macro_rules::{self, MacroRules};
+
+// What a call expression resolved to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Callee {
+    Unsafe,
+    UnsafeTraitMethod,
+    Safe,
+    // Not declared in the crate, or declared both safe and unsafe.
+    Unresolved,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Safety {
+    safe: bool,
+    unsafe_fn: bool,
+    unsafe_trait_method: bool,
+}
+
+impl Safety {
+    fn add(&mut self, sig: &Signature, trait_method: bool) {
+        match (sig.unsafety.is_some(), trait_method) {
+            (false, _) => self.safe = true,
+            (true, false) => self.unsafe_fn = true,
+            (true, true) => self.unsafe_trait_method = true,
+        }
+    }
+
+    fn callee(self) -> Callee {
+        match (self.safe, self.unsafe_fn, self.unsafe_trait_method) {
+            (true, false, false) => Callee::Safe,
+            (false, true, false) => Callee::Unsafe,
+            (false, false, true) => Callee::UnsafeTraitMethod,
+            _ => Callee::Unresolved,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Field {
+    ptr: bool,
+    non_ptr: bool,
+    union: bool,
+    non_union: bool,
+}
+
+// The `use` declarations of a module. Targets are absolute (`crate::...`)
+// where that can be determined, and otherwise kept as written.
+#[derive(Default)]
+struct Imports {
+    aliases: HashMap<String, Vec<Vec<String>>>,
+    globs: Vec<Vec<String>>,
+}
+
+// Crate-wide table of function declarations, built from every file of the
+// crate before any of them is analysed. Functions and trait methods are keyed
+// by their module path (`crate::ffi::Trait::method`), inherent and trait impl
+// methods by `Type::method` and, for method call syntax, by name alone.
+#[derive(Default)]
+pub struct SymbolTable {
+    // The name other crates in the package use to refer to this one.
+    crate_name: Option<String>,
+    items: HashMap<String, Safety>,
+    assoc: HashMap<String, Safety>,
+    methods: HashMap<String, Safety>,
+    imports: HashMap<Vec<String>, Imports>,
+    unsafe_statics: HashSet<String>,
+    fields: HashMap<String, Field>,
+    // `macro_rules!` definitions by name, `None` for those the expander
+    // cannot handle.
+    macros: HashMap<String, Option<MacroRules>>,
+}
+
+impl SymbolTable {
+    pub fn new(crate_name: Option<String>) -> SymbolTable {
+        SymbolTable {
+            crate_name,
+            ..SymbolTable::default()
+        }
+    }
+
+    // Adds the `macro_rules!` definitions of a file. Called for every file
+    // of the crate before `add_file`, so that macro invocations declaring
+    // functions can be expanded wherever the macro is defined.
+    pub fn add_macros(&mut self, file: &syn::File) {
+        MacroCollector(&mut self.macros).visit_file(file);
+    }
+
+    // Adds the declarations of a file whose items live in `module`, leaving
+    // out those that `cfg` rules out.
+    pub fn add_file(&mut self, file: &syn::File, module: &[String], cfg: &CfgSet) {
+        let mut collector = Collector {
+            table: self,
+            cfg,
+            module: module.to_vec(),
+            self_ty: None,
+            trait_impl: false,
+            depth: 0,
+        };
+        collector.visit_file(file);
+    }
+
+    // Expands an invocation of a crate-local `macro_rules!` macro. Returns
+    // `None` for other macros and `Some(None)` if the expansion failed.
+    pub fn expand_macro(&self, mac: &Macro) -> Option<Option<TokenStream>> {
+        let name = mac.path.get_ident()?.to_string();
+        let rules = self.macros.get(&name)?.as_ref();
+        let call_site = mac.path.segments[0].ident.span();
+        Some(rules.and_then(|rules| rules.expand(&mac.tokens, call_site)))
+    }
+
+    // Resolves the function called by a path expression such as `foo(x)`,
+    // `module::foo(x)` or `Type::method(x)`.
+    pub fn resolve_call(&self, module: &[String], self_ty: Option<&str>, path: &Path) -> Callee {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let name = segments.last().unwrap();
+
+        let callee = if segments[0] == "Self" {
+            match (self_ty, segments.len()) {
+                (Some(self_ty), 2) => {
+                    let mut trait_item = module.to_vec();
+                    trait_item.extend([self_ty.to_string(), name.clone()]);
+                    self.lookup(&trait_item)
+                        .or_else(|| self.assoc(self_ty, name))
+                        .unwrap_or(Callee::Unresolved)
+                }
+                _ => Callee::Unresolved,
+            }
+        } else if path.leading_colon.is_some() {
+            Callee::Unresolved
+        } else {
+            self.candidates(module, &segments)
+                .iter()
+                .find_map(|candidate| self.lookup(candidate))
+                .unwrap_or(Callee::Unresolved)
+        };
+        // Tuple structs and enum variants are called like functions, but
+        // declared functions such as `GetLastError` may be named like them.
+        match callee {
+            Callee::Unresolved if name.starts_with(char::is_uppercase) => Callee::Safe,
+            callee => callee,
+        }
+    }
+
+    // The paths outside of the crate that `path`, written inside `module`,
+    // may refer to: the path as written and its expansions through `use`
+    // aliases. Glob imports are not followed.
+    pub fn external_paths(&self, module: &[String], path: &Path) -> Vec<Vec<String>> {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        let (first, rest) = segments.split_first().unwrap();
+        if absolute(module, &segments).is_some() || first == "Self" {
+            return Vec::new();
+        }
+
+        let mut paths = vec![segments.clone()];
+        let imports = self.imports.get(module);
+        if let Some(targets) = imports.and_then(|imports| imports.aliases.get(first)) {
+            for target in targets {
+                if target[0] != "crate" {
+                    paths.push(join(target, rest));
+                }
+            }
+        }
+        paths
+    }
+
+    // Resolves a method call such as `x.foo()` by the method name alone.
+    pub fn resolve_method(&self, name: &str) -> Callee {
+        match self.methods.get(name) {
+            Some(safety) => safety.callee(),
+            None => Callee::Unresolved,
+        }
+    }
+
+    // A `static mut` or a static declared in an extern block.
+    pub fn is_unsafe_static(&self, name: &str) -> bool {
+        self.unsafe_statics.contains(name)
+    }
+
+    // Whether every field with this name holds a raw pointer.
+    pub fn is_ptr_field(&self, name: &str) -> bool {
+        self.fields
+            .get(name)
+            .is_some_and(|field| field.ptr && !field.non_ptr)
+    }
+
+    // Whether every field with this name belongs to a union.
+    pub fn is_union_field(&self, name: &str) -> bool {
+        self.fields
+            .get(name)
+            .is_some_and(|field| field.union && !field.non_union)
+    }
+
+    fn lookup(&self, path: &[String]) -> Option<Callee> {
+        if path.first().map(String::as_str) != Some("crate") {
+            return None;
+        }
+        if let Some(safety) = self.items.get(&path.join("::")) {
+            return Some(safety.callee());
+        }
+        match path {
+            [.., ty, name] => self.assoc(ty, name),
+            _ => None,
+        }
+    }
+
+    fn assoc(&self, ty: &str, name: &str) -> Option<Callee> {
+        let safety = self.assoc.get(&format!("{}::{}", ty, name))?;
+        Some(safety.callee())
+    }
+
+    // The crate-rooted paths that `path`, written inside `module`, may refer
+    // to. Paths into other crates have no candidates.
+    fn candidates(&self, module: &[String], path: &[String]) -> Vec<Vec<String>> {
+        let root = ["crate".to_string()];
+        let (first, rest) = path.split_first().unwrap();
+        if let Some(absolute) = absolute(module, path) {
+            return vec![absolute];
+        }
+        if Some(first) == self.crate_name.as_ref() {
+            return vec![join(&root, rest)];
+        }
+
+        let mut candidates = Vec::new();
+        let imports = self.imports.get(module);
+        if let Some(targets) = imports.and_then(|imports| imports.aliases.get(first)) {
+            for target in targets {
+                let target = join(target, rest);
+                if target[0] == "crate" {
+                    candidates.push(target);
+                } else {
+                    candidates.push(join(module, &target));
+                    candidates.push(join(&root, &target));
+                }
+            }
+            return candidates;
+        }
+
+        candidates.push(join(module, path));
+        for glob in imports.iter().flat_map(|imports| &imports.globs) {
+            if glob[0] == "crate" {
+                candidates.push(join(glob, path));
+            } else {
+                candidates.push(join(&join(module, glob), path));
+            }
+        }
+        // Paths relative to the crate root, as in the 2015 edition.
+        candidates.push(join(&root, path));
+        candidates
+    }
+
+    fn add_field(&mut self, name: String, ty: &Type, union: bool) {
+        let field = self.fields.entry(name).or_default();
+        if is_ptr_type(ty) {
+            field.ptr = true;
+        } else {
+            field.non_ptr = true;
+        }
+        if union {
+            field.union = true;
+        } else {
+            field.non_union = true;
+        }
+    }
+}
+
+pub fn is_ptr_type(ty: &Type) -> bool {
+    match ty {
+        Type::Ptr(_) => true,
+        Type::Paren(ty) => is_ptr_type(&ty.elem),
+        Type::Group(ty) => is_ptr_type(&ty.elem),
+        _ => false,
+    }
+}
+
+// The name of the type an impl block is for, ignoring generics and paths.
+pub fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => Some(path.path.segments.last()?.ident.to_string()),
+        Type::Paren(ty) => type_name(&ty.elem),
+        Type::Group(ty) => type_name(&ty.elem),
+        _ => None,
+    }
+}
+
+fn join(prefix: &[String], rest: &[String]) -> Vec<String> {
+    prefix.iter().chain(rest).cloned().collect()
+}
+
+// Resolves `crate::`, `self::` and `super::` prefixes.
+fn absolute(module: &[String], path: &[String]) -> Option<Vec<String>> {
+    match path.first()?.as_str() {
+        "crate" => Some(path.to_vec()),
+        "self" => Some(join(module, &path[1..])),
+        "super" => {
+            let supers = path.iter().take_while(|s| *s == "super").count();
+            let parent = module.len().checked_sub(supers).filter(|&n| n > 0)?;
+            Some(join(&module[..parent], &path[supers..]))
+        }
+        _ => None,
+    }
+}
+
+fn has_receiver(sig: &Signature) -> bool {
+    matches!(sig.inputs.first(), Some(FnArg::Receiver(_)))
+}
+
+struct MacroCollector<'t>(&'t mut HashMap<String, Option<MacroRules>>);
+
+impl<'ast> Visit<'ast> for MacroCollector<'_> {
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        if let Some(ident) = &node.ident {
+            if node.mac.path.is_ident("macro_rules") {
+                let rules = MacroRules::parse(&node.mac.tokens);
+                self.0.insert(ident.to_string(), rules);
+            }
+        }
+    }
+}
+
+struct Collector<'t> {
+    table: &'t mut SymbolTable,
+    cfg: &'t CfgSet,
+    module: Vec<String>,
+    // The type of the enclosing impl, or the enclosing trait.
+    self_ty: Option<String>,
+    trait_impl: bool,
+    // Nesting of the macro expansions being visited.
+    depth: usize,
+}
+
+impl Collector<'_> {
+    fn add_item(&mut self, name: String, sig: &Signature, trait_method: bool) {
+        let path = join(&self.module, &[name]).join("::");
+        let safety = self.table.items.entry(path).or_default();
+        safety.add(sig, trait_method);
+    }
+
+    fn add_use(&mut self, prefix: &mut Vec<String>, tree: &UseTree) {
+        let (name, target) = match tree {
+            UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.add_use(prefix, &path.tree);
+                prefix.pop();
+                return;
+            }
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.add_use(prefix, tree);
+                }
+                return;
+            }
+            UseTree::Glob(_) => {
+                let glob = absolute(&self.module, prefix).unwrap_or_else(|| prefix.clone());
+                let imports = self.table.imports.entry(self.module.clone()).or_default();
+                imports.globs.push(glob);
+                return;
+            }
+            UseTree::Name(name) if name.ident == "self" => match prefix.last() {
+                Some(last) => (last.clone(), prefix.clone()),
+                None => return,
+            },
+            UseTree::Name(name) => {
+                let name = name.ident.to_string();
+                (name.clone(), join(prefix, &[name]))
+            }
+            UseTree::Rename(rename) if rename.ident == "self" => {
+                (rename.rename.to_string(), prefix.clone())
+            }
+            UseTree::Rename(rename) => (
+                rename.rename.to_string(),
+                join(prefix, &[rename.ident.to_string()]),
+            ),
+        };
+        let target = absolute(&self.module, &target).unwrap_or(target);
+        let imports = self.table.imports.entry(self.module.clone()).or_default();
+        imports.aliases.entry(name).or_default().push(target);
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item(&mut self, node: &'ast Item) {
+        if self.cfg.is_active(cfg::item_attrs(node)) {
+            visit::visit_item(self, node);
+        }
+    }
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        if self.cfg.is_active(cfg::impl_item_attrs(node)) {
+            visit::visit_impl_item(self, node);
+        }
+    }
+    fn visit_trait_item(&mut self, node: &'ast TraitItem) {
+        if self.cfg.is_active(cfg::trait_item_attrs(node)) {
+            visit::visit_trait_item(self, node);
+        }
+    }
+    fn visit_foreign_item(&mut self, node: &'ast ForeignItem) {
+        if self.cfg.is_active(cfg::foreign_item_attrs(node)) {
+            visit::visit_foreign_item(self, node);
+        }
+    }
+    // Functions declared by crate-local macros are declared like any other.
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        if node.ident.is_some() || self.depth >= macro_rules::MAX_DEPTH {
+            return;
+        }
+        let expansion = self.table.expand_macro(&node.mac).flatten();
+        if let Some(items) = expansion.and_then(macro_rules::parse_items) {
+            self.depth += 1;
+            for item in &items {
+                self.visit_item(item);
+            }
+            self.depth -= 1;
+        }
+    }
+    fn visit_impl_item_macro(&mut self, node: &'ast ImplItemMacro) {
+        if self.depth >= macro_rules::MAX_DEPTH {
+            return;
+        }
+        let expansion = self.table.expand_macro(&node.mac).flatten();
+        if let Some(items) = expansion.and_then(macro_rules::parse_impl_items) {
+            self.depth += 1;
+            for item in &items {
+                self.visit_impl_item(item);
+            }
+            self.depth -= 1;
+        }
+    }
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        if node.content.is_some() {
+            self.module.push(node.ident.to_string());
+            visit::visit_item_mod(self, node);
+            self.module.pop();
+        }
+    }
+    fn visit_item_use(&mut self, node: &'ast ItemUse) {
+        if node.leading_colon.is_none() {
+            self.add_use(&mut Vec::new(), &node.tree);
+        }
+    }
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.add_item(node.sig.ident.to_string(), &node.sig, false);
+        visit::visit_item_fn(self, node);
+    }
+    fn visit_foreign_item_fn(&mut self, node: &'ast ForeignItemFn) {
+        // Foreign functions are always unsafe to call.
+        let path = join(&self.module, &[node.sig.ident.to_string()]).join("::");
+        self.table.items.entry(path).or_default().unsafe_fn = true;
+    }
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        let self_ty = self.self_ty.take();
+        let trait_impl = self.trait_impl;
+        self.self_ty = type_name(&node.self_ty);
+        self.trait_impl = node.trait_.is_some();
+        visit::visit_item_impl(self, node);
+        self.self_ty = self_ty;
+        self.trait_impl = trait_impl;
+    }
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        let name = node.sig.ident.to_string();
+        if let Some(self_ty) = &self.self_ty {
+            let key = format!("{}::{}", self_ty, name);
+            let safety = self.table.assoc.entry(key).or_default();
+            safety.add(&node.sig, self.trait_impl);
+        }
+        if has_receiver(&node.sig) {
+            let safety = self.table.methods.entry(name).or_default();
+            safety.add(&node.sig, self.trait_impl);
+        }
+        visit::visit_impl_item_fn(self, node);
+    }
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        let self_ty = self.self_ty.replace(node.ident.to_string());
+        visit::visit_item_trait(self, node);
+        self.self_ty = self_ty;
+    }
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        let name = node.sig.ident.to_string();
+        if let Some(trait_name) = self.self_ty.clone() {
+            self.add_item(format!("{}::{}", trait_name, name), &node.sig, true);
+        }
+        if has_receiver(&node.sig) {
+            let safety = self.table.methods.entry(name).or_default();
+            safety.add(&node.sig, true);
+        }
+        visit::visit_trait_item_fn(self, node);
+    }
+    fn visit_item_static(&mut self, node: &'ast ItemStatic) {
+        if let StaticMutability::Mut(_) = node.mutability {
+            self.table.unsafe_statics.insert(node.ident.to_string());
+        }
+        visit::visit_item_static(self, node);
+    }
+    fn visit_foreign_item_static(&mut self, node: &'ast ForeignItemStatic) {
+        self.table.unsafe_statics.insert(node.ident.to_string());
+    }
+    fn visit_item_struct(&mut self, node: &'ast ItemStruct) {
+        for (i, field) in node.fields.iter().enumerate() {
+            let name = match &field.ident {
+                Some(ident) => ident.to_string(),
+                None => i.to_string(),
+            };
+            self.table.add_field(name, &field.ty, false);
+        }
+        visit::visit_item_struct(self, node);
+    }
+    fn visit_item_union(&mut self, node: &'ast ItemUnion) {
+        for field in &node.fields.named {
+            let name = field.ident.as_ref().unwrap().to_string();
+            self.table.add_field(name, &field.ty, true);
+        }
+        visit::visit_item_union(self, node);
+    }
+}
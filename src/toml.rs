@@ -0,0 +1,662 @@
+// A small TOML reader, sufficient for Cargo manifests, lockfiles and the
+// rustalyzer configuration file. Datetimes are kept as plain strings.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Datetime(String),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_table().and_then(|table| table.get(key))
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Table, Error> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Error> {
+        Err(Error {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{}`", c))
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    // Skips whitespace, comments and newlines, as allowed between array
+    // elements and between top-level expressions.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.skip_whitespace();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.error(format!("unexpected `{}` at end of line", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Table, Error> {
+        let mut root = Table::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None => return Ok(root),
+                Some('[') => {
+                    let line = self.line;
+                    self.bump();
+                    let array = self.eat('[');
+                    self.skip_whitespace();
+                    let path = self.key()?;
+                    self.skip_whitespace();
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                    }
+                    self.end_of_line()?;
+                    let (last, parent) = path.split_last().unwrap();
+                    let parent = table_at(&mut root, parent, line)?;
+                    let message = if array {
+                        let entry = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        match entry {
+                            Value::Array(tables) => {
+                                tables.push(Value::Table(Table::new()));
+                                None
+                            }
+                            _ => Some(format!("`{}` is not an array", last)),
+                        }
+                    } else {
+                        let entry = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Table(Table::new()));
+                        match entry {
+                            Value::Table(_) => None,
+                            _ => Some(format!("`{}` is not a table", last)),
+                        }
+                    };
+                    if let Some(message) = message {
+                        return Err(Error { line, message });
+                    }
+                    current = path;
+                }
+                Some(_) => {
+                    let line = self.line;
+                    let (key, value) = self.key_value()?;
+                    let table = table_at(&mut root, &current, line)?;
+                    insert(table, &key, value, line)?;
+                    self.end_of_line()?;
+                }
+            }
+        }
+    }
+
+    fn key_value(&mut self) -> Result<(Vec<String>, Value), Error> {
+        let key = self.key()?;
+        self.skip_whitespace();
+        self.expect('=')?;
+        self.skip_whitespace();
+        let value = self.value()?;
+        Ok((key, value))
+    }
+
+    fn key(&mut self) -> Result<Vec<String>, Error> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let part = match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    self.basic_string()?
+                }
+                Some('\'') => {
+                    self.bump();
+                    self.literal_string()?
+                }
+                _ => {
+                    let mut part = String::new();
+                    while let Some(c) = self.peek() {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                            part.push(c);
+                            self.bump();
+                        } else {
+                            break;
+                        }
+                    }
+                    if part.is_empty() {
+                        return self.error("expected a key");
+                    }
+                    part
+                }
+            };
+            path.push(part);
+            self.skip_whitespace();
+            if !self.eat('.') {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some('"') => {
+                if self.starts_with("\"\"\"") {
+                    self.pos += 3;
+                    self.multiline_basic_string().map(Value::String)
+                } else {
+                    self.bump();
+                    self.basic_string().map(Value::String)
+                }
+            }
+            Some('\'') => {
+                if self.starts_with("'''") {
+                    self.pos += 3;
+                    self.multiline_literal_string().map(Value::String)
+                } else {
+                    self.bump();
+                    self.literal_string().map(Value::String)
+                }
+            }
+            Some('[') => {
+                self.bump();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_trivia();
+                    if self.eat(']') {
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_trivia();
+                    if !self.eat(',') {
+                        self.skip_trivia();
+                        self.expect(']')?;
+                        return Ok(Value::Array(values));
+                    }
+                }
+            }
+            Some('{') => {
+                self.bump();
+                let mut table = Table::new();
+                self.skip_whitespace();
+                if self.eat('}') {
+                    return Ok(Value::Table(table));
+                }
+                loop {
+                    let line = self.line;
+                    let (key, value) = self.key_value()?;
+                    insert(&mut table, &key, value, line)?;
+                    self.skip_whitespace();
+                    if self.eat('}') {
+                        return Ok(Value::Table(table));
+                    }
+                    self.expect(',')?;
+                    self.skip_whitespace();
+                }
+            }
+            Some(_) => self.scalar(),
+            None => self.error("expected a value"),
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Value, Error> {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.' | ':') {
+                token.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        match token.as_str() {
+            "" => return self.error("expected a value"),
+            "true" => return Ok(Value::Boolean(true)),
+            "false" => return Ok(Value::Boolean(false)),
+            "inf" | "+inf" => return Ok(Value::Float(f64::INFINITY)),
+            "-inf" => return Ok(Value::Float(f64::NEG_INFINITY)),
+            "nan" | "+nan" | "-nan" => return Ok(Value::Float(f64::NAN)),
+            _ => {}
+        }
+        let digits = token.replace('_', "");
+        let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+            .iter()
+            .find(|(prefix, _)| digits.starts_with(prefix));
+        if let Some((prefix, radix)) = radix {
+            return match i64::from_str_radix(&digits[prefix.len()..], *radix) {
+                Ok(n) => Ok(Value::Integer(n)),
+                Err(_) => self.error(format!("invalid integer `{}`", token)),
+            };
+        }
+        if let Ok(n) = digits.parse::<i64>() {
+            return Ok(Value::Integer(n));
+        }
+        if let Ok(x) = digits.parse::<f64>() {
+            return Ok(Value::Float(x));
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) && token.contains([':', '-']) {
+            return Ok(Value::Datetime(token));
+        }
+        self.error(format!("invalid value `{}`", token))
+    }
+
+    fn escape(&mut self) -> Result<char, Error> {
+        let c = match self.bump() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let mut hex = String::new();
+                for _ in 0..len {
+                    match self.bump() {
+                        Some(c) => hex.push(c),
+                        None => return self.error("unterminated string"),
+                    }
+                }
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return self.error(format!("invalid unicode escape `{}`", hex)),
+                }
+            }
+            Some(c) => return self.error(format!("invalid escape `\\{}`", c)),
+            None => return self.error("unterminated string"),
+        };
+        Ok(c)
+    }
+
+    fn basic_string(&mut self) -> Result<String, Error> {
+        // Reported on the line the string is on, not the one after it.
+        let line = self.line;
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some('\n') | None => {
+                    return Err(Error {
+                        line,
+                        message: "unterminated string".to_string(),
+                    })
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, Error> {
+        let line = self.line;
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(s),
+                Some('\n') | None => {
+                    return Err(Error {
+                        line,
+                        message: "unterminated string".to_string(),
+                    })
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String, Error> {
+        let mut s = String::new();
+        self.skip_leading_newline();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.bump() {
+                Some('\\') => {
+                    if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        while let Some(' ' | '\t' | '\r' | '\n') = self.peek() {
+                            self.bump();
+                        }
+                    } else {
+                        s.push(self.escape()?);
+                    }
+                }
+                Some(c) => s.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, Error> {
+        let mut s = String::new();
+        self.skip_leading_newline();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.bump() {
+                Some(c) => s.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn skip_leading_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        self.eat('\n');
+    }
+}
+
+fn table_at<'t>(
+    mut table: &'t mut Table,
+    path: &[String],
+    line: usize,
+) -> Result<&'t mut Table, Error> {
+    for key in path {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(table) => table,
+            Value::Array(values) => match values.last_mut() {
+                Some(Value::Table(table)) => table,
+                _ => {
+                    return Err(Error {
+                        line,
+                        message: format!("`{}` is not an array of tables", key),
+                    })
+                }
+            },
+            _ => {
+                return Err(Error {
+                    line,
+                    message: format!("`{}` is not a table", key),
+                })
+            }
+        };
+    }
+    Ok(table)
+}
+
+fn insert(table: &mut Table, key: &[String], value: Value, line: usize) -> Result<(), Error> {
+    let (last, parent) = key.split_last().unwrap();
+    let table = table_at(table, parent, line)?;
+    if table.contains_key(last) {
+        return Err(Error {
+            line,
+            message: format!("duplicate key `{}`", last),
+        });
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> String {
+        parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn quoted_and_dotted_keys() {
+        let table = parse(
+            r#"
+"quoted key" = 1
+'literal.key' = 2
+a."b.c" . d = 3
+"#,
+        )
+        .unwrap();
+        assert_eq!(table["quoted key"], Value::Integer(1));
+        assert_eq!(table["literal.key"], Value::Integer(2));
+        assert_eq!(
+            table["a"].get("b.c").and_then(|t| t.get("d")),
+            Some(&Value::Integer(3))
+        );
+    }
+
+    #[test]
+    fn inline_tables() {
+        let table = parse(r#"dep = { version = "1.0", features = ["std"], a.b = true }"#).unwrap();
+        let dep = &table["dep"];
+        assert_eq!(dep.get("version").and_then(Value::as_str), Some("1.0"));
+        assert_eq!(
+            dep.get("features").and_then(Value::as_array),
+            Some(&[Value::String("std".to_string())][..])
+        );
+        assert_eq!(
+            dep.get("a").and_then(|a| a.get("b")),
+            Some(&Value::Boolean(true))
+        );
+        assert_eq!(parse("t = {}").unwrap()["t"], Value::Table(Table::new()));
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let table = parse(
+            r#"
+[[package]]
+name = "a"
+[package.source]
+kind = "git"
+
+[[package]]
+name = "b"
+"#,
+        )
+        .unwrap();
+        let packages = table["package"].as_array().unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].get("name").and_then(Value::as_str), Some("a"));
+        assert_eq!(
+            packages[0].get("source").and_then(|s| s.get("kind")),
+            Some(&Value::String("git".to_string()))
+        );
+        assert_eq!(packages[1].get("name").and_then(Value::as_str), Some("b"));
+        assert_eq!(packages[1].get("source"), None);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let table = parse(
+            r#"
+a = "tab\there \"quoted\" \u00e9\U0001F600"
+b = 'C:\path'
+c = """
+  first \
+    second"""
+d = '''
+raw \n'''
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            table["a"].as_str(),
+            Some("tab\there \"quoted\" \u{e9}\u{1F600}")
+        );
+        assert_eq!(table["b"].as_str(), Some(r"C:\path"));
+        assert_eq!(table["c"].as_str(), Some("  first second"));
+        assert_eq!(table["d"].as_str(), Some(r"raw \n"));
+    }
+
+    #[test]
+    fn scalars() {
+        let table = parse(
+            r#"
+int = 1_000
+hex = 0xff
+float = -1.5e3
+bool = false
+date = 2024-01-02T03:04:05Z
+"#,
+        )
+        .unwrap();
+        assert_eq!(table["int"], Value::Integer(1000));
+        assert_eq!(table["hex"], Value::Integer(255));
+        assert_eq!(table["float"], Value::Float(-1500.0));
+        assert_eq!(table["bool"], Value::Boolean(false));
+        assert_eq!(
+            table["date"],
+            Value::Datetime("2024-01-02T03:04:05Z".to_string())
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("a = 1\nb = \"open\n"), "line 2: unterminated string");
+        assert_eq!(error(r#"a = "\q""#), r"line 1: invalid escape `\q`");
+        assert_eq!(error(r#"a = "\u12""#), "line 1: unterminated string");
+        assert_eq!(error("a = 1\n\na = 2\n"), "line 3: duplicate key `a`");
+        assert_eq!(error("a = 1\n[a]\n"), "line 2: `a` is not a table");
+        assert_eq!(error("[a]\n[[a]]\n"), "line 2: `a` is not an array");
+        assert_eq!(error("a = 1\n[a.b]\n"), "line 2: `a` is not a table");
+        assert_eq!(error("a = [1, 2\n"), "line 2: expected `]`");
+        assert_eq!(
+            error("a = 1 b = 2"),
+            "line 1: unexpected `b` at end of line"
+        );
+        assert_eq!(error("a = 1x"), "line 1: invalid value `1x`");
+        assert_eq!(error("= 1"), "line 1: expected a key");
+    }
+}
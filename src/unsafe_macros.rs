@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use proc_macro2::{LineColumn, TokenStream, TokenTree};
+use syn::visit::{self, Visit};
+use syn::{ItemMacro, Macro};
+
+// A `macro_rules!` definition whose transcribers contain `unsafe`, found by
+// scanning its tokens rather than expanding it.
+pub struct UnsafeMacro {
+    pub name: String,
+    pub unsafe_tokens: usize,
+    // Invocations across the crate, filled in once every file is scanned.
+    pub invocations: usize,
+    pub start: LineColumn,
+}
+
+impl Display for UnsafeMacro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "macro_rules! {} ({} `unsafe` token{}, {} invocation{})",
+            self.name,
+            self.unsafe_tokens,
+            plural(self.unsafe_tokens),
+            self.invocations,
+            plural(self.invocations)
+        )
+    }
+}
+
+// Returns the `macro_rules!` definitions of a file that emit `unsafe`.
+pub fn definitions(file: &syn::File) -> Vec<UnsafeMacro> {
+    let mut definitions = Definitions(Vec::new());
+    definitions.visit_file(file);
+    definitions.0
+}
+
+// Counts the macro invocations of a file by macro name, including those
+// nested in the input of other macros.
+pub fn count_invocations(file: &syn::File, counts: &mut HashMap<String, usize>) {
+    Invocations(counts).visit_file(file);
+}
+
+struct Definitions(Vec<UnsafeMacro>);
+
+impl<'ast> Visit<'ast> for Definitions {
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        let ident = match &node.ident {
+            Some(ident) if node.mac.path.is_ident("macro_rules") => ident,
+            _ => return visit::visit_item_macro(self, node),
+        };
+        let unsafe_tokens = transcriber_unsafe_tokens(&node.mac.tokens);
+        if unsafe_tokens > 0 {
+            self.0.push(UnsafeMacro {
+                name: ident.to_string(),
+                unsafe_tokens,
+                invocations: 0,
+                start: node.mac.path.segments[0].ident.span().start(),
+            });
+        }
+    }
+}
+
+// Counts `unsafe` in the transcribers of a `macro_rules!` body, which are the
+// groups following each `=>`. Bodies without any rule are scanned whole.
+fn transcriber_unsafe_tokens(body: &TokenStream) -> usize {
+    let tokens: Vec<TokenTree> = body.clone().into_iter().collect();
+    let mut rules = 0;
+    let mut count = 0;
+    for window in tokens.windows(3) {
+        if let [TokenTree::Punct(eq), TokenTree::Punct(gt), TokenTree::Group(group)] = window {
+            if eq.as_char() == '=' && gt.as_char() == '>' {
+                rules += 1;
+                count += unsafe_tokens(&group.stream());
+            }
+        }
+    }
+    if rules == 0 {
+        return unsafe_tokens(body);
+    }
+    count
+}
+
+fn unsafe_tokens(tokens: &TokenStream) -> usize {
+    tokens
+        .clone()
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ident) if ident == "unsafe" => 1,
+            TokenTree::Group(group) => unsafe_tokens(&group.stream()),
+            _ => 0,
+        })
+        .sum()
+}
+
+struct Invocations<'c>(&'c mut HashMap<String, usize>);
+
+impl Invocations<'_> {
+    // Finds `name!(...)` in tokens that were not parsed.
+    fn scan(&mut self, tokens: &TokenStream) {
+        let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Ident(ident) => {
+                    let bang = matches!(
+                        tokens.get(i + 1),
+                        Some(TokenTree::Punct(punct)) if punct.as_char() == '!'
+                    );
+                    let group = matches!(tokens.get(i + 2), Some(TokenTree::Group(_)));
+                    if bang && group {
+                        *self.0.entry(ident.to_string()).or_default() += 1;
+                    }
+                }
+                TokenTree::Group(group) => self.scan(&group.stream()),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Invocations<'_> {
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        // Invocations inside a definition only happen when it is expanded.
+        if node.ident.is_none() {
+            visit::visit_item_macro(self, node);
+        }
+    }
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if let Some(segment) = node.path.segments.last() {
+            *self.0.entry(segment.ident.to_string()).or_default() += 1;
+        }
+        self.scan(&node.tokens);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::{self, Display};
+use std::ops::AddAssign;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnsafeOp {
+    RawPtrDeref,
+    UnsafeCall,
+    UnsafeTraitMethodCall,
+    StaticAccess,
+    UnionFieldRead,
+    Asm,
+}
+
+impl UnsafeOp {
+    const ALL: [UnsafeOp; 6] = [
+        UnsafeOp::RawPtrDeref,
+        UnsafeOp::UnsafeCall,
+        UnsafeOp::UnsafeTraitMethodCall,
+        UnsafeOp::StaticAccess,
+        UnsafeOp::UnionFieldRead,
+        UnsafeOp::Asm,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            UnsafeOp::RawPtrDeref => "raw pointer deref",
+            UnsafeOp::UnsafeCall => "unsafe fn call",
+            UnsafeOp::UnsafeTraitMethodCall => "unsafe trait method call",
+            UnsafeOp::StaticAccess => "static mut access",
+            UnsafeOp::UnionFieldRead => "union field read",
+            UnsafeOp::Asm => "asm!",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct OpCounts([usize; UnsafeOp::ALL.len()]);
+
+impl OpCounts {
+    pub fn add(&mut self, op: UnsafeOp) {
+        self.0[op as usize] += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+}
+
+impl AddAssign for OpCounts {
+    fn add_assign(&mut self, other: OpCounts) {
+        for (count, other) in self.0.iter_mut().zip(other.0) {
+            *count += other;
+        }
+    }
+}
+
+// Formats as e.g. `3 (raw pointer deref 2, unsafe fn call 1)`.
+impl Display for OpCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.total())?;
+        let breakdown: Vec<String> = UnsafeOp::ALL
+            .iter()
+            .filter(|&&op| self.0[op as usize] > 0)
+            .map(|&op| format!("{} {}", op.description(), self.0[op as usize]))
+            .collect();
+        if !breakdown.is_empty() {
+            write!(f, " ({})", breakdown.join(", "))?;
+        }
+        Ok(())
+    }
+}
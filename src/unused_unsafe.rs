@@ -0,0 +1,69 @@
+use std::fmt::{self, Display};
+
+use proc_macro2::LineColumn;
+
+pub enum UnusedUnsafeKind {
+    // An `unsafe {}` block without any detectable unsafe operation.
+    EmptyBlock,
+    // An `unsafe {}` block directly inside another unsafe context, with the
+    // span of the `unsafe` keyword of that context.
+    NestedBlock {
+        in_unsafe_fn: bool,
+        outer: (LineColumn, LineColumn),
+    },
+    // An `unsafe fn` whose body performs no unsafe operation.
+    EmptyFn,
+}
+
+// An `unsafe` keyword that makes no difference to what the compiler accepts.
+pub struct UnusedUnsafe {
+    pub kind: UnusedUnsafeKind,
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl UnusedUnsafe {
+    pub fn is_fn(&self) -> bool {
+        matches!(self.kind, UnusedUnsafeKind::EmptyFn)
+    }
+
+    pub fn header(&self) -> &'static str {
+        match self.kind {
+            UnusedUnsafeKind::EmptyFn => "`unsafe fn` performs no unsafe operations",
+            _ => "unnecessary `unsafe` block",
+        }
+    }
+
+    // The `unsafe` keyword that a nested block is already covered by, and
+    // the label pointing at it.
+    pub fn outer(&self) -> Option<(LineColumn, LineColumn, &'static str)> {
+        match self.kind {
+            UnusedUnsafeKind::NestedBlock {
+                in_unsafe_fn,
+                outer: (start, end),
+            } => {
+                let label = if in_unsafe_fn {
+                    "because it's nested under this `unsafe` fn"
+                } else {
+                    "because it's nested under this `unsafe` block"
+                };
+                Some((start, end, label))
+            }
+            _ => None,
+        }
+    }
+}
+
+// The label under the `unsafe` keyword.
+impl Display for UnusedUnsafe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            UnusedUnsafeKind::EmptyBlock => f.write_str("no unsafe operations"),
+            UnusedUnsafeKind::NestedBlock { in_unsafe_fn, .. } => {
+                let context = if in_unsafe_fn { "fn" } else { "block" };
+                write!(f, "already inside an `unsafe` {}", context)
+            }
+            UnusedUnsafeKind::EmptyFn => f.write_str("no unsafe operations in its body"),
+        }
+    }
+}
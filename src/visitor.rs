@@ -0,0 +1,974 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::mem;
+use std::ops::AddAssign;
+
+use proc_macro2::{LineColumn, Span, TokenStream};
+use syn::visit::{self, Visit};
+use syn::{
+    Arm, Attribute, Block, Expr, ExprAssign, ExprCall, ExprClosure, ExprField, ExprForLoop,
+    ExprMacro, ExprMethodCall, ExprPath, ExprRepeat, ExprUnary, ExprUnsafe, FieldValue, File,
+    FnArg, ForeignItem, ImplItem, ImplItemConst, ImplItemFn, ImplItemMacro, Item, ItemConst,
+    ItemFn, ItemForeignMod, ItemImpl, ItemMacro, ItemMod, ItemStatic, ItemTrait, Local, Macro,
+    Member, Pat, PatIdent, QSelf, Signature, Stmt, StmtMacro, TraitItem, TraitItemConst,
+    TraitItemFn, TypeArray, UnOp,
+};
+
+use crate::catalog::Catalog;
+use crate::cfg::{self, Cfg, CfgSet};
+use crate::ffi::{self, FfiItem};
+use crate::inventory::UnsafeItem;
+use crate::macro_rules;
+use crate::macros::{MacroBody, Macros};
+use crate::manifest::Edition;
+use crate::pretty;
+use crate::role::Role;
+use crate::symbols::{self, Callee, SymbolTable};
+use crate::unsafe_ops::{OpCounts, UnsafeOp};
+use crate::unused_unsafe::{UnusedUnsafe, UnusedUnsafeKind};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Accounting {
+    // The body of an `unsafe fn` is an unsafe context (editions before 2024).
+    UnsafeFnBodies,
+    // Edition 2024 `unsafe_op_in_unsafe_fn`: only `unsafe {}` blocks count.
+    UnsafeBlocksOnly,
+}
+
+impl Accounting {
+    pub fn for_edition(edition: Edition) -> Accounting {
+        if edition >= Edition::E2024 {
+            Accounting::UnsafeBlocksOnly
+        } else {
+            Accounting::UnsafeFnBodies
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    count: usize,
+    // Unsafe statements under the accounting of the analysed file(s).
+    unsafe_count: usize,
+    fn_body_unsafe_count: usize,
+    block_unsafe_count: usize,
+    // Statements containing at least one unsafe operation.
+    op_stmt_count: usize,
+    pub ops: OpCounts,
+    // Calls in unsafe contexts whose safety could not be determined.
+    pub unresolved_calls: usize,
+    // Invocations of crate-local `macro_rules!` macros that could not be
+    // expanded.
+    pub unexpanded_macros: usize,
+    // Which accountings contributed to these stats.
+    unsafe_fn_bodies: bool,
+    unsafe_blocks_only: bool,
+}
+
+impl Stats {
+    fn new(accounting: Accounting) -> Stats {
+        Stats {
+            unsafe_fn_bodies: accounting == Accounting::UnsafeFnBodies,
+            unsafe_blocks_only: accounting == Accounting::UnsafeBlocksOnly,
+            ..Stats::default()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count == 0
+            && self.ops.total() == 0
+            && self.unresolved_calls == 0
+            && self.unexpanded_macros == 0
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.count += other.count;
+        self.unsafe_count += other.unsafe_count;
+        self.fn_body_unsafe_count += other.fn_body_unsafe_count;
+        self.block_unsafe_count += other.block_unsafe_count;
+        self.op_stmt_count += other.op_stmt_count;
+        self.ops += other.ops;
+        self.unresolved_calls += other.unresolved_calls;
+        self.unexpanded_macros += other.unexpanded_macros;
+        self.unsafe_fn_bodies |= other.unsafe_fn_bodies;
+        self.unsafe_blocks_only |= other.unsafe_blocks_only;
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.unsafe_count, self.count)?;
+        let mut alternatives = Vec::new();
+        if self.unsafe_fn_bodies {
+            alternatives.push(format!(
+                "unsafe blocks only: {}/{}",
+                self.block_unsafe_count, self.count
+            ));
+        }
+        if self.unsafe_blocks_only {
+            alternatives.push(format!(
+                "with unsafe fn bodies: {}/{}",
+                self.fn_body_unsafe_count, self.count
+            ));
+        }
+        alternatives.push(format!(
+            "performing unsafe operations: {}/{}",
+            self.op_stmt_count, self.count
+        ));
+        write!(f, " ({})", alternatives.join(", "))
+    }
+}
+
+// The statements of a function or method, including those of the closures
+// within it but not of the fns nested in it.
+pub struct FnStats {
+    // The path of the fn, starting with the module path of the file.
+    pub path: Vec<String>,
+    pub start: LineColumn,
+    pub declared_unsafe: bool,
+    pub role: Role,
+    pub count: usize,
+    pub unsafe_count: usize,
+    pub unsafe_blocks: usize,
+}
+
+impl Display for FnStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let blocks = match self.unsafe_blocks {
+            1 => "block",
+            _ => "blocks",
+        };
+        write!(
+            f,
+            "{}/{} ({} unsafe {})",
+            self.unsafe_count, self.count, self.unsafe_blocks, blocks
+        )
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct UnsafeContext {
+    unsafe_fn: bool,
+    unsafe_block: bool,
+    // The `unsafe` keyword of the innermost unsafe fn or block.
+    unsafety: Option<Span>,
+}
+
+// Local bindings in scope, mapped to whether they are known raw pointers.
+type Scope = HashMap<String, bool>;
+
+pub struct StmtVisitor<'a> {
+    accounting: Accounting,
+    symbols: &'a SymbolTable,
+    catalog: &'a Catalog,
+    macros: &'a Macros,
+    module: Vec<String>,
+    // The type of the enclosing impl, or the enclosing trait.
+    self_ty: Option<String>,
+    // The names of the enclosing impls, traits and fns within `module`.
+    items: Vec<String>,
+    pub stats: Stats,
+    pub functions: Vec<FnStats>,
+    // The index of the enclosing fn in `functions`.
+    function: Option<usize>,
+    // Whether the items being visited are in a trait impl, whose methods
+    // are `unsafe` because the trait requires it.
+    trait_impl: bool,
+    pub unsafe_items: Vec<UnsafeItem>,
+    pub ffi_items: Vec<FfiItem>,
+    context: UnsafeContext,
+    locals: Vec<Scope>,
+    // Enclosing statements, and whether each performs an unsafe operation.
+    stmts: Vec<bool>,
+    // Enclosing `unsafe` blocks and fns, and whether each contains an unsafe
+    // operation or something that may hide one.
+    regions: Vec<bool>,
+    pub unused_unsafe: Vec<UnusedUnsafe>,
+    // Nesting of the macro expansions being visited.
+    depth: usize,
+    cfg: &'a CfgSet,
+    // Whether to keep the stats of code behind `cfg` predicates apart.
+    by_cfg: bool,
+    // The enclosing predicates, with `by_cfg`.
+    cfgs: Vec<Cfg>,
+    // The role of the enclosing code, initially that of the file.
+    pub role: Role,
+    // Whether `stats` only counts production code.
+    pub production_only: bool,
+    // Stats by role and by the conjunction of the enclosing predicates.
+    // `stats` only holds those gathered since the last change of either.
+    buckets: BTreeMap<(Role, Option<String>), Stats>,
+    // Stats by the conjunction of the enclosing predicates, `None` for code
+    // behind none. Only filled with `by_cfg`.
+    pub cfg_stats: BTreeMap<Option<String>, Stats>,
+    pub role_stats: BTreeMap<Role, Stats>,
+    // The predicates of the `mod` declarations leading to the file, which
+    // apply to all of it.
+    pub file_cfgs: Vec<Cfg>,
+    // Set when the predicates of the next expression or item were already
+    // evaluated by the statement containing it.
+    cfg_checked: bool,
+}
+
+impl<'a> StmtVisitor<'a> {
+    pub fn new(
+        accounting: Accounting,
+        symbols: &'a SymbolTable,
+        catalog: &'a Catalog,
+        macros: &'a Macros,
+        module: Vec<String>,
+        cfg: &'a CfgSet,
+        by_cfg: bool,
+    ) -> StmtVisitor<'a> {
+        StmtVisitor {
+            accounting,
+            symbols,
+            catalog,
+            macros,
+            module,
+            self_ty: None,
+            items: Vec::new(),
+            stats: Stats::new(accounting),
+            functions: Vec::new(),
+            function: None,
+            trait_impl: false,
+            unsafe_items: Vec::new(),
+            ffi_items: Vec::new(),
+            context: UnsafeContext::default(),
+            locals: Vec::new(),
+            stmts: Vec::new(),
+            regions: Vec::new(),
+            unused_unsafe: Vec::new(),
+            depth: 0,
+            cfg,
+            by_cfg,
+            cfgs: Vec::new(),
+            role: Role::Production,
+            production_only: false,
+            buckets: BTreeMap::new(),
+            cfg_stats: BTreeMap::new(),
+            role_stats: BTreeMap::new(),
+            file_cfgs: Vec::new(),
+            cfg_checked: false,
+        }
+    }
+
+    fn record(&mut self, op: UnsafeOp) {
+        if self.context.unsafe_fn || self.context.unsafe_block {
+            self.stats.ops.add(op);
+            for performs_op in &mut self.stmts {
+                *performs_op = true;
+            }
+            self.use_regions();
+        }
+    }
+
+    fn use_regions(&mut self) {
+        for used in &mut self.regions {
+            *used = true;
+        }
+    }
+
+    fn is_unsafe_context(&self) -> bool {
+        match self.accounting {
+            Accounting::UnsafeFnBodies => self.context.unsafe_fn || self.context.unsafe_block,
+            Accounting::UnsafeBlocksOnly => self.context.unsafe_block,
+        }
+    }
+
+    fn call(&mut self, callee: Callee) {
+        match callee {
+            Callee::Unsafe => self.record(UnsafeOp::UnsafeCall),
+            Callee::UnsafeTraitMethod => self.record(UnsafeOp::UnsafeTraitMethodCall),
+            Callee::Safe => {}
+            Callee::Unresolved => {
+                if self.context.unsafe_fn || self.context.unsafe_block {
+                    self.stats.unresolved_calls += 1;
+                    self.use_regions();
+                }
+            }
+        }
+    }
+
+    fn with_scope<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.locals.push(Scope::new());
+        f(self);
+        self.locals.pop();
+    }
+
+    fn local(&self, name: &str) -> Option<bool> {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    // Binds the identifiers of `pat` in the innermost scope. Only a plain
+    // identifier pattern can be known to hold a raw pointer.
+    fn bind(&mut self, pat: &Pat, ptr: bool) {
+        let scope = match self.locals.last_mut() {
+            Some(scope) => scope,
+            None => return,
+        };
+        match pat {
+            Pat::Ident(PatIdent {
+                ident,
+                subpat: None,
+                ..
+            }) => {
+                scope.insert(ident.to_string(), ptr);
+            }
+            Pat::Type(pat) => self.bind(&pat.pat, ptr || symbols::is_ptr_type(&pat.ty)),
+            _ => {
+                let mut idents = PatIdents(Vec::new());
+                idents.visit_pat(pat);
+                for ident in idents.0 {
+                    scope.insert(ident, false);
+                }
+            }
+        }
+    }
+
+    // Whether `expr` is determinably of raw pointer type.
+    fn is_ptr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Cast(cast) => symbols::is_ptr_type(&cast.ty),
+            Expr::Paren(paren) => self.is_ptr(&paren.expr),
+            Expr::Group(group) => self.is_ptr(&group.expr),
+            Expr::Path(path) => match path.path.get_ident() {
+                Some(ident) => self.local(&ident.to_string()) == Some(true),
+                None => false,
+            },
+            Expr::Field(field) => match &field.member {
+                Member::Named(ident) => self.symbols.is_ptr_field(&ident.to_string()),
+                Member::Unnamed(index) => self.symbols.is_ptr_field(&index.index.to_string()),
+            },
+            Expr::MethodCall(call) => match call.method.to_string().as_str() {
+                "as_ptr" | "as_mut_ptr" => true,
+                "add" | "sub" | "offset" | "wrapping_add" | "wrapping_sub" | "wrapping_offset"
+                | "byte_add" | "byte_sub" | "cast" | "cast_mut" | "cast_const" => {
+                    self.is_ptr(&call.receiver)
+                }
+                _ => false,
+            },
+            Expr::Call(call) => {
+                match &*call.func {
+                    Expr::Path(path) => path.path.segments.last().is_some_and(|segment| {
+                        segment.ident == "null" || segment.ident == "null_mut"
+                    }),
+                    _ => false,
+                }
+            }
+            Expr::Macro(mac) => mac.mac.path.segments.last().is_some_and(|segment| {
+                segment.ident == "addr_of" || segment.ident == "addr_of_mut"
+            }),
+            _ => false,
+        }
+    }
+
+    // Visits the expansion of an invocation of a crate-local `macro_rules!`
+    // macro, parsed with `parse`. Returns false for other macros.
+    fn expand<T, P, F>(&mut self, mac: &Macro, parse: P, visit: F) -> bool
+    where
+        P: FnOnce(TokenStream) -> Option<T>,
+        F: FnOnce(&mut Self, &T),
+    {
+        let expansion = match self.symbols.expand_macro(mac) {
+            Some(expansion) => expansion,
+            None => return false,
+        };
+        let parsed = match expansion {
+            Some(tokens) if self.depth < macro_rules::MAX_DEPTH => parse(tokens),
+            _ => None,
+        };
+        match parsed {
+            Some(parsed) => {
+                self.depth += 1;
+                visit(self, &parsed);
+                self.depth -= 1;
+            }
+            None => {
+                self.stats.unexpanded_macros += 1;
+                self.use_regions();
+            }
+        }
+        true
+    }
+
+    // Resolves a call through a path against the crate's own declarations
+    // first, then the catalog of external unsafe APIs.
+    fn resolve_path(&self, qself: Option<&QSelf>, path: &syn::Path) -> Callee {
+        let name = path.segments.last().unwrap().ident.to_string();
+        let callee = match qself {
+            None => self
+                .symbols
+                .resolve_call(&self.module, self.self_ty.as_deref(), path),
+            // `<T as Trait>::method`
+            Some(qself) if qself.position > 0 => {
+                self.symbols
+                    .resolve_call(&self.module, self.self_ty.as_deref(), path)
+            }
+            // `<*const T>::add` and `<[T]>::get_unchecked`
+            Some(qself) => {
+                let ptr = symbols::is_ptr_type(&qself.ty);
+                return self
+                    .catalog
+                    .resolve_method(&name, ptr)
+                    .unwrap_or(Callee::Unresolved);
+            }
+        };
+        if callee != Callee::Unresolved {
+            return callee;
+        }
+        let paths = self.symbols.external_paths(&self.module, path);
+        if let Some(callee) = self.catalog.resolve_call(&paths) {
+            return callee;
+        }
+        // Methods called with a type-qualified path, as in `Vec::set_len(v, 0)`.
+        if path.segments.len() > 1 {
+            if let Some(callee) = self.catalog.resolve_method(&name, false) {
+                return callee;
+            }
+        }
+        Callee::Unresolved
+    }
+
+    // Visits a node unless its `cfg` predicates are known to be false,
+    // accounting it to the role its attributes give it.
+    fn with_attrs<F>(&mut self, attrs: &[Attribute], f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        if mem::take(&mut self.cfg_checked) {
+            return f(self);
+        }
+        let cfgs = self.cfg.predicates(attrs);
+        let role = self.role.nested(attrs, &cfgs);
+        self.with_role(role, |v| v.with_cfgs(cfgs, f));
+    }
+
+    fn with_cfgs<F>(&mut self, cfgs: Vec<Cfg>, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        if cfgs.iter().any(|cfg| self.cfg.eval(cfg) == Some(false)) {
+            return;
+        }
+        if !self.by_cfg || cfgs.is_empty() {
+            return f(self);
+        }
+        self.flush();
+        let depth = self.cfgs.len();
+        self.cfgs.extend(cfgs);
+        f(self);
+        self.flush();
+        self.cfgs.truncate(depth);
+    }
+
+    fn with_role<F>(&mut self, role: Role, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        if role == self.role {
+            return f(self);
+        }
+        self.flush();
+        let outer = mem::replace(&mut self.role, role);
+        f(self);
+        self.flush();
+        self.role = outer;
+    }
+
+    // Moves the stats gathered since the last change of role or predicates
+    // to the bucket of the code they were gathered in.
+    fn flush(&mut self) {
+        let cfg = match self.cfgs.is_empty() {
+            true => None,
+            false => Some(Cfg::all(&self.cfgs).to_string()),
+        };
+        let stats = mem::replace(&mut self.stats, Stats::new(self.accounting));
+        if !stats.is_empty() {
+            *self.buckets.entry((self.role, cfg)).or_default() += stats;
+        }
+    }
+
+    // Runs `f` with the given unsafe context, restoring the enclosing one
+    // afterwards. Unsafety is lexically scoped like in rustc: nested items and
+    // anonymous constants start out safe, closures inherit their surroundings.
+    fn with_context<F>(&mut self, context: UnsafeContext, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let outer = mem::replace(&mut self.context, context);
+        f(self);
+        self.context = outer;
+    }
+
+    fn with_safe<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.with_context(UnsafeContext::default(), f);
+    }
+
+    fn with_fn<F>(&mut self, sig: &Signature, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let context = UnsafeContext {
+            unsafe_fn: sig.unsafety.is_some(),
+            unsafe_block: false,
+            unsafety: sig.unsafety.as_ref().map(|unsafety| unsafety.span),
+        };
+        self.items.push(sig.ident.to_string());
+        self.functions.push(FnStats {
+            path: [&self.module[..], &self.items].concat(),
+            start: sig.fn_token.span.start(),
+            declared_unsafe: sig.unsafety.is_some(),
+            role: self.role,
+            count: 0,
+            unsafe_count: 0,
+            unsafe_blocks: 0,
+        });
+        let function = self.function.replace(self.functions.len() - 1);
+        let trait_method = mem::take(&mut self.trait_impl);
+        let outer = mem::replace(&mut self.locals, vec![Scope::new()]);
+        let stmts = mem::take(&mut self.stmts);
+        let regions = mem::replace(&mut self.regions, vec![false]);
+        for input in &sig.inputs {
+            if let FnArg::Typed(arg) = input {
+                self.bind(&arg.pat, symbols::is_ptr_type(&arg.ty));
+            }
+        }
+        self.with_context(context, f);
+        let unused = self.regions.pop() == Some(false) && !trait_method;
+        if let (Some(unsafety), true) = (&sig.unsafety, unused) {
+            self.unused_unsafe.push(UnusedUnsafe {
+                kind: UnusedUnsafeKind::EmptyFn,
+                start: unsafety.span.start(),
+                end: unsafety.span.end(),
+            });
+        }
+        self.locals = outer;
+        self.stmts = stmts;
+        self.regions = regions;
+        self.items.pop();
+        self.function = function;
+        self.trait_impl = trait_method;
+    }
+}
+
+struct PatIdents(Vec<String>);
+
+impl<'ast> Visit<'ast> for PatIdents {
+    fn visit_pat_ident(&mut self, node: &'ast PatIdent) {
+        self.0.push(node.ident.to_string());
+        visit::visit_pat_ident(self, node);
+    }
+}
+
+impl<'ast, 'a> Visit<'ast> for StmtVisitor<'a> {
+    fn visit_expr_unsafe(&mut self, node: &'ast ExprUnsafe) {
+        let span = node.unsafe_token.span;
+        let nested = self.is_unsafe_context();
+        if let (true, Some(outer)) = (nested, self.context.unsafety) {
+            self.unused_unsafe.push(UnusedUnsafe {
+                kind: UnusedUnsafeKind::NestedBlock {
+                    in_unsafe_fn: !self.context.unsafe_block,
+                    outer: (outer.start(), outer.end()),
+                },
+                start: span.start(),
+                end: span.end(),
+            });
+        }
+        let context = UnsafeContext {
+            unsafe_block: true,
+            unsafety: Some(span),
+            ..self.context
+        };
+        if let Some(function) = self.function {
+            self.functions[function].unsafe_blocks += 1;
+        }
+        self.regions.push(false);
+        self.with_context(context, |v| visit::visit_expr_unsafe(v, node));
+        if self.regions.pop() == Some(false) && !nested {
+            self.unused_unsafe.push(UnusedUnsafe {
+                kind: UnusedUnsafeKind::EmptyBlock,
+                start: span.start(),
+                end: span.end(),
+            });
+        }
+    }
+    fn visit_expr_repeat(&mut self, node: &'ast ExprRepeat) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        self.visit_expr(&node.expr);
+        self.with_safe(|v| v.visit_expr(&node.len));
+    }
+    fn visit_type_array(&mut self, node: &'ast TypeArray) {
+        self.visit_type(&node.elem);
+        self.with_safe(|v| v.visit_expr(&node.len));
+    }
+    fn visit_file(&mut self, node: &'ast File) {
+        let cfgs = mem::take(&mut self.file_cfgs);
+        let role = self.role.nested(&node.attrs, &cfgs);
+        self.with_role(role, |v| v.with_cfgs(cfgs, |v| visit::visit_file(v, node)));
+        self.flush();
+        for ((role, cfg), stats) in mem::take(&mut self.buckets) {
+            *self.role_stats.entry(role).or_default() += stats;
+            if self.production_only && role != Role::Production {
+                continue;
+            }
+            if self.by_cfg {
+                *self.cfg_stats.entry(cfg).or_default() += stats;
+            }
+            self.stats += stats;
+        }
+    }
+    fn visit_item(&mut self, node: &'ast Item) {
+        self.with_attrs(cfg::item_attrs(node), |v| {
+            let locals = mem::take(&mut v.locals);
+            let stmts = mem::take(&mut v.stmts);
+            let regions = mem::take(&mut v.regions);
+            let function = v.function.take();
+            v.with_safe(|v| visit::visit_item(v, node));
+            v.locals = locals;
+            v.stmts = stmts;
+            v.regions = regions;
+            v.function = function;
+        });
+    }
+    fn visit_impl_item(&mut self, node: &'ast ImplItem) {
+        self.with_attrs(cfg::impl_item_attrs(node), |v| {
+            visit::visit_impl_item(v, node)
+        });
+    }
+    fn visit_trait_item(&mut self, node: &'ast TraitItem) {
+        self.with_attrs(cfg::trait_item_attrs(node), |v| {
+            visit::visit_trait_item(v, node)
+        });
+    }
+    fn visit_foreign_item(&mut self, node: &'ast ForeignItem) {
+        self.with_attrs(cfg::foreign_item_attrs(node), |v| {
+            visit::visit_foreign_item(v, node)
+        });
+    }
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        self.with_attrs(cfg::expr_attrs(node), |v| visit::visit_expr(v, node));
+    }
+    fn visit_field_value(&mut self, node: &'ast FieldValue) {
+        self.with_attrs(&node.attrs, |v| visit::visit_field_value(v, node));
+    }
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.ffi_items
+            .extend(ffi::exported_fn(&node.attrs, &node.sig));
+        self.with_fn(&node.sig, |v| visit::visit_item_fn(v, node));
+    }
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        if node.content.is_some() {
+            self.module.push(node.ident.to_string());
+            visit::visit_item_mod(self, node);
+            self.module.pop();
+        }
+    }
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        if node.unsafety.is_some() {
+            self.unsafe_items.push(UnsafeItem::from_impl(node));
+        }
+        let self_ty = mem::replace(&mut self.self_ty, symbols::type_name(&node.self_ty));
+        let trait_impl = mem::replace(&mut self.trait_impl, node.trait_.is_some());
+        self.items.push(pretty::impl_name(node));
+        visit::visit_item_impl(self, node);
+        self.items.pop();
+        self.self_ty = self_ty;
+        self.trait_impl = trait_impl;
+    }
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        if node.unsafety.is_some() {
+            self.unsafe_items.push(UnsafeItem::from_trait(node));
+        }
+        let self_ty = self.self_ty.replace(node.ident.to_string());
+        self.items.push(node.ident.to_string());
+        visit::visit_item_trait(self, node);
+        self.items.pop();
+        self.self_ty = self_ty;
+    }
+    fn visit_item_foreign_mod(&mut self, node: &'ast ItemForeignMod) {
+        self.ffi_items.extend(ffi::foreign_mod(node));
+        visit::visit_item_foreign_mod(self, node);
+    }
+    fn visit_item_const(&mut self, node: &'ast ItemConst) {
+        self.with_safe(|v| visit::visit_item_const(v, node));
+    }
+    fn visit_item_static(&mut self, node: &'ast ItemStatic) {
+        self.ffi_items.extend(ffi::exported_static(node));
+        self.with_safe(|v| visit::visit_item_static(v, node));
+    }
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.ffi_items
+            .extend(ffi::exported_fn(&node.attrs, &node.sig));
+        self.with_fn(&node.sig, |v| visit::visit_impl_item_fn(v, node));
+    }
+    fn visit_impl_item_const(&mut self, node: &'ast ImplItemConst) {
+        self.with_safe(|v| visit::visit_impl_item_const(v, node));
+    }
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        // Only a default body can perform unsafe operations.
+        if node.default.is_none() {
+            return visit::visit_trait_item_fn(self, node);
+        }
+        self.with_fn(&node.sig, |v| visit::visit_trait_item_fn(v, node));
+    }
+    fn visit_trait_item_const(&mut self, node: &'ast TraitItemConst) {
+        self.with_safe(|v| visit::visit_trait_item_const(v, node));
+    }
+    fn visit_block(&mut self, node: &'ast Block) {
+        self.with_scope(|v| visit::visit_block(v, node));
+    }
+    fn visit_local(&mut self, node: &'ast Local) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        let mut ptr = false;
+        if let Some(init) = &node.init {
+            self.visit_expr(&init.expr);
+            if let Some((_, diverge)) = &init.diverge {
+                self.visit_expr(diverge);
+            }
+            ptr = self.is_ptr(&init.expr);
+        }
+        self.bind(&node.pat, ptr);
+        self.visit_pat(&node.pat);
+    }
+    fn visit_expr_closure(&mut self, node: &'ast ExprClosure) {
+        self.with_scope(|v| {
+            for input in &node.inputs {
+                v.bind(input, false);
+            }
+            visit::visit_expr_closure(v, node);
+        });
+    }
+    fn visit_arm(&mut self, node: &'ast Arm) {
+        self.with_attrs(&node.attrs, |v| {
+            v.with_scope(|v| {
+                v.bind(&node.pat, false);
+                visit::visit_arm(v, node);
+            })
+        });
+    }
+    fn visit_expr_for_loop(&mut self, node: &'ast ExprForLoop) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        self.visit_expr(&node.expr);
+        self.with_scope(|v| {
+            v.bind(&node.pat, false);
+            v.visit_pat(&node.pat);
+            v.visit_block(&node.body);
+        });
+    }
+    fn visit_expr_unary(&mut self, node: &'ast ExprUnary) {
+        if let UnOp::Deref(_) = node.op {
+            if self.is_ptr(&node.expr) {
+                self.record(UnsafeOp::RawPtrDeref);
+            }
+        }
+        visit::visit_expr_unary(self, node);
+    }
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        let callee = match &*node.func {
+            Expr::Path(path) => {
+                let segments = &path.path.segments;
+                let name = segments.last().unwrap().ident.to_string();
+                match &path.qself {
+                    // A closure or function pointer held in a local binding.
+                    None if segments.len() == 1 && self.local(&name).is_some() => None,
+                    qself => Some(self.resolve_path(qself.as_ref(), &path.path)),
+                }
+            }
+            _ => Some(Callee::Unresolved),
+        };
+        if let Some(callee) = callee {
+            self.call(callee);
+        }
+        visit::visit_expr_call(self, node);
+    }
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let name = node.method.to_string();
+        let ptr_receiver = self.is_ptr(&node.receiver);
+        let callee = match self.catalog.resolve_method(&name, ptr_receiver) {
+            // No crate can declare inherent methods on raw pointers.
+            Some(callee) if ptr_receiver => callee,
+            external => match self.symbols.resolve_method(&name) {
+                Callee::Unresolved => external.unwrap_or(Callee::Unresolved),
+                callee => callee,
+            },
+        };
+        self.call(callee);
+        visit::visit_expr_method_call(self, node);
+    }
+    fn visit_expr_path(&mut self, node: &'ast ExprPath) {
+        let name = node.path.segments.last().unwrap().ident.to_string();
+        let local = node.path.segments.len() == 1 && self.local(&name).is_some();
+        if !local && node.qself.is_none() && self.symbols.is_unsafe_static(&name) {
+            self.record(UnsafeOp::StaticAccess);
+        }
+        visit::visit_expr_path(self, node);
+    }
+    fn visit_expr_field(&mut self, node: &'ast ExprField) {
+        if let Member::Named(ident) = &node.member {
+            if self.symbols.is_union_field(&ident.to_string()) {
+                self.record(UnsafeOp::UnionFieldRead);
+            }
+        }
+        visit::visit_expr_field(self, node);
+    }
+    fn visit_expr_assign(&mut self, node: &'ast ExprAssign) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        // Writing to a union field is safe, only reading it is not.
+        match &*node.left {
+            Expr::Field(field) => {
+                for attr in &field.attrs {
+                    self.visit_attribute(attr);
+                }
+                self.visit_expr(&field.base);
+                self.visit_member(&field.member);
+            }
+            left => self.visit_expr(left),
+        }
+        self.visit_expr(&node.right);
+    }
+    fn visit_stmt_macro(&mut self, node: &'ast StmtMacro) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        let expanded = self.expand(&node.mac, macro_rules::parse_stmts, |v, stmts| {
+            v.with_scope(|v| {
+                for stmt in stmts {
+                    v.visit_stmt(stmt);
+                }
+            })
+        });
+        if !expanded {
+            self.visit_macro(&node.mac);
+        }
+    }
+    fn visit_expr_macro(&mut self, node: &'ast ExprMacro) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        let expanded = self.expand(&node.mac, macro_rules::parse_expr, |v, expr| {
+            v.visit_expr(expr);
+        });
+        if !expanded {
+            self.visit_macro(&node.mac);
+        }
+    }
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        // A `macro_rules!` definition, only visited through its invocations.
+        if node.ident.is_some() {
+            return;
+        }
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        let expanded = self.expand(&node.mac, macro_rules::parse_items, |v, items| {
+            for item in items {
+                v.visit_item(item);
+            }
+        });
+        if !expanded {
+            self.visit_macro(&node.mac);
+        }
+    }
+    fn visit_impl_item_macro(&mut self, node: &'ast ImplItemMacro) {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        let expanded = self.expand(&node.mac, macro_rules::parse_impl_items, |v, items| {
+            for item in items {
+                v.visit_impl_item(item);
+            }
+        });
+        if !expanded {
+            self.visit_macro(&node.mac);
+        }
+    }
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if let Some(segment) = node.path.segments.last() {
+            if segment.ident == "asm" || segment.ident == "llvm_asm" {
+                self.record(UnsafeOp::Asm);
+            }
+        }
+        match self.macros.parse(node) {
+            Some(MacroBody::Exprs(exprs)) => {
+                for expr in &exprs {
+                    self.visit_expr(expr);
+                }
+            }
+            Some(MacroBody::Stmts(stmts)) => self.with_scope(|v| {
+                for stmt in &stmts {
+                    v.visit_stmt(stmt);
+                }
+            }),
+            Some(MacroBody::Items(items)) => {
+                for item in &items {
+                    self.visit_item(item);
+                }
+            }
+            // The macro's input is opaque, so it may hide unsafe operations.
+            None => self.use_regions(),
+        }
+        visit::visit_macro(self, node);
+    }
+    fn visit_stmt(&mut self, node: &'ast Stmt) {
+        let attrs = match node {
+            Stmt::Local(local) => &local.attrs,
+            Stmt::Item(item) => cfg::item_attrs(item),
+            Stmt::Expr(expr, _) => cfg::expr_attrs(expr),
+            Stmt::Macro(mac) => &mac.attrs,
+        };
+        self.with_attrs(attrs, |v| {
+            let UnsafeContext {
+                unsafe_fn,
+                unsafe_block,
+                ..
+            } = v.context;
+            v.stats.count += 1;
+            if unsafe_fn || unsafe_block {
+                v.stats.fn_body_unsafe_count += 1;
+            }
+            if unsafe_block {
+                v.stats.block_unsafe_count += 1;
+            }
+            let is_unsafe = match v.accounting {
+                Accounting::UnsafeFnBodies => unsafe_fn || unsafe_block,
+                Accounting::UnsafeBlocksOnly => unsafe_block,
+            };
+            if is_unsafe {
+                v.stats.unsafe_count += 1;
+            }
+            if let Some(function) = v.function {
+                v.functions[function].count += 1;
+                if is_unsafe {
+                    v.functions[function].unsafe_count += 1;
+                }
+            }
+            v.stmts.push(false);
+            // The expression or item is the statement's first child.
+            v.cfg_checked = matches!(node, Stmt::Item(_) | Stmt::Expr(..));
+            visit::visit_stmt(v, node);
+            if v.stmts.pop() == Some(true) {
+                v.stats.op_stmt_count += 1;
+            }
+        });
+    }
+}
@@ -0,0 +1,183 @@
+// Expands directory inputs into the Rust files below them, skipping what the
+// `.gitignore` and `.rustalyzerignore` files along the way exclude.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+
+const IGNORE_FILES: &[&str] = &[".gitignore", ".rustalyzerignore"];
+
+// The `--exclude` and `--include` globs, relative to each directory input.
+#[derive(Default)]
+pub struct Filters {
+    pub exclude: Vec<Glob>,
+    pub include: Vec<Glob>,
+}
+
+impl Filters {
+    fn excluded(&self, path: &str, is_dir: bool) -> bool {
+        self.exclude.iter().any(|glob| glob.matches(path, is_dir))
+    }
+
+    // Files are included if they or one of their directories match.
+    fn included(&self, path: &str) -> bool {
+        if self.include.is_empty() {
+            return true;
+        }
+        let dirs = path.match_indices('/').map(|(i, _)| (&path[..i], true));
+        let mut candidates = dirs.chain([(path, false)]);
+        candidates.any(|(path, is_dir)| self.include.iter().any(|glob| glob.matches(path, is_dir)))
+    }
+}
+
+struct IgnoreFile {
+    globs: Vec<Glob>,
+    // The path of the walked directory relative to the ignore file's, for
+    // ignore files above it.
+    prefix: String,
+    // The number of leading components of a path relative to the walked
+    // directory to drop, for ignore files below it.
+    depth: usize,
+}
+
+impl IgnoreFile {
+    fn read(dir: &Path, prefix: String, depth: usize) -> Vec<IgnoreFile> {
+        IGNORE_FILES
+            .iter()
+            .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .map(|src| IgnoreFile {
+                // Lines that are not valid globs are skipped like git does.
+                globs: src
+                    .lines()
+                    .map(str::trim_end)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .filter_map(|line| Glob::new(line).ok())
+                    .collect(),
+                prefix: prefix.clone(),
+                depth,
+            })
+            .collect()
+    }
+}
+
+// Returns `input` itself if it is a file, or the `.rs` files below it in a
+// stable order if it is a directory. Entries of the directory that cannot be
+// read are added to `errors` with their paths and left out of the walk.
+pub fn rust_files(
+    input: &Path,
+    filters: &Filters,
+    errors: &mut Vec<(PathBuf, io::Error)>,
+) -> io::Result<Vec<PathBuf>> {
+    if !fs::metadata(input)?.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+    let mut ignores = ancestor_ignores(input);
+    let mut files = Vec::new();
+    walk(
+        input,
+        &mut Vec::new(),
+        &mut ignores,
+        filters,
+        &mut files,
+        errors,
+    );
+    Ok(files)
+}
+
+// Ignore files in the directories between the repository root and `dir`.
+fn ancestor_ignores(dir: &Path) -> Vec<IgnoreFile> {
+    let dir = match fs::canonicalize(dir) {
+        Ok(dir) => dir,
+        Err(_) => return Vec::new(),
+    };
+    let root = match dir.ancestors().skip(1).find(|a| a.join(".git").exists()) {
+        Some(root) if !dir.join(".git").exists() => root,
+        _ => return Vec::new(),
+    };
+    let mut ignores = Vec::new();
+    for ancestor in dir.ancestors().skip(1) {
+        let prefix = dir.strip_prefix(ancestor).unwrap();
+        let prefix = components(prefix).join("/") + "/";
+        let mut files = IgnoreFile::read(ancestor, prefix, 0);
+        files.append(&mut ignores);
+        ignores = files;
+        if ancestor == root {
+            break;
+        }
+    }
+    ignores
+}
+
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect()
+}
+
+fn is_ignored(ignores: &[IgnoreFile], path: &[String], is_dir: bool) -> bool {
+    let mut ignored = false;
+    for file in ignores {
+        let relative = file.prefix.clone() + &path[file.depth..].join("/");
+        for glob in &file.globs {
+            if glob.matches(&relative, is_dir) {
+                ignored = !glob.negated;
+            }
+        }
+    }
+    ignored
+}
+
+fn walk(
+    dir: &Path,
+    relative: &mut Vec<String>,
+    ignores: &mut Vec<IgnoreFile>,
+    filters: &Filters,
+    files: &mut Vec<PathBuf>,
+    errors: &mut Vec<(PathBuf, io::Error)>,
+) {
+    let mut entries = Vec::new();
+    match fs::read_dir(dir) {
+        Ok(read) => {
+            for entry in read {
+                match entry {
+                    Ok(entry) => entries.push(entry),
+                    Err(error) => errors.push((dir.to_path_buf(), error)),
+                }
+            }
+        }
+        Err(error) => return errors.push((dir.to_path_buf(), error)),
+    }
+    let mut local = IgnoreFile::read(dir, String::new(), relative.len());
+    let pushed = local.len();
+    ignores.append(&mut local);
+
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Symlinked directories are not followed, to avoid cycles.
+        let is_dir = match entry.file_type() {
+            Ok(file_type) => file_type.is_dir(),
+            Err(error) => {
+                errors.push((entry.path(), error));
+                continue;
+            }
+        };
+        if is_dir && name == ".git" {
+            continue;
+        }
+        relative.push(name);
+        let path = relative.join("/");
+        if !is_ignored(ignores, relative, is_dir) && !filters.excluded(&path, is_dir) {
+            if is_dir {
+                walk(&entry.path(), relative, ignores, filters, files, errors);
+            } else if path.ends_with(".rs") && filters.included(&path) {
+                files.push(entry.path());
+            }
+        }
+        relative.pop();
+    }
+
+    ignores.truncate(ignores.len() - pushed);
+}
@@ -0,0 +1,975 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn rustalyzer(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustalyzer"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run rustalyzer");
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Runs rustalyzer on inputs some of which cannot be analysed, returning
+// stdout and stderr.
+fn rustalyzer_failing(args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustalyzer"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run rustalyzer");
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn counts(fixture: &str) -> String {
+    counts_with(fixture, &[])
+}
+
+fn counts_with(fixture: &str, flags: &[&str]) -> String {
+    let path = format!("tests/fixtures/{}", fixture);
+    let mut args = flags.to_vec();
+    args.push(&path);
+    let stdout = rustalyzer(&args);
+    let line = stdout.lines().next().unwrap();
+    line.strip_prefix(&format!("{}: ", path))
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn unsafe_free_fn() {
+    assert_eq!(
+        counts("unsafe_fns/free_fn.rs"),
+        "2/4 (unsafe blocks only: 0/4, performing unsafe operations: 1/4)"
+    );
+}
+
+#[test]
+fn unsafe_inherent_method() {
+    assert_eq!(
+        counts("unsafe_fns/inherent_method.rs"),
+        "2/3 (unsafe blocks only: 0/3, performing unsafe operations: 1/3)"
+    );
+}
+
+#[test]
+fn unsafe_trait_impl_method() {
+    assert_eq!(
+        counts("unsafe_fns/trait_impl_method.rs"),
+        "2/2 (unsafe blocks only: 0/2, performing unsafe operations: 1/2)"
+    );
+}
+
+#[test]
+fn unsafe_trait_default_method() {
+    assert_eq!(
+        counts("unsafe_fns/trait_default.rs"),
+        "2/3 (unsafe blocks only: 0/3, performing unsafe operations: 0/3)"
+    );
+}
+
+#[test]
+fn unsafe_nested_fn() {
+    assert_eq!(
+        counts("unsafe_fns/nested_fn.rs"),
+        "3/6 (unsafe blocks only: 1/6, performing unsafe operations: 3/6)"
+    );
+}
+
+#[test]
+fn safe_fn_inside_unsafe_fn() {
+    assert_eq!(
+        counts("scoping/fn_in_unsafe_fn.rs"),
+        "2/4 (unsafe blocks only: 0/4, performing unsafe operations: 1/4)"
+    );
+}
+
+#[test]
+fn safe_fn_inside_unsafe_block() {
+    assert_eq!(
+        counts("scoping/fn_in_unsafe_block.rs"),
+        "2/5 (unsafe blocks only: 2/5, performing unsafe operations: 2/5)"
+    );
+}
+
+#[test]
+fn closure_inherits_unsafe_block() {
+    assert_eq!(
+        counts("scoping/closure.rs"),
+        "4/5 (unsafe blocks only: 4/5, performing unsafe operations: 3/5)"
+    );
+}
+
+#[test]
+fn const_and_static_initializers_are_safe() {
+    assert_eq!(
+        counts("scoping/const_static.rs"),
+        "4/10 (unsafe blocks only: 0/10, performing unsafe operations: 0/10)"
+    );
+}
+
+#[test]
+fn edition_inherited_from_workspace() {
+    assert_eq!(
+        counts("workspace/member/src/lib.rs"),
+        "1/3 (with unsafe fn bodies: 3/3, performing unsafe operations: 2/3)"
+    );
+}
+
+#[test]
+fn edition_flag_overrides_manifest() {
+    assert_eq!(
+        counts_with("workspace/member/src/lib.rs", &["--edition", "2021"]),
+        "3/3 (unsafe blocks only: 1/3, performing unsafe operations: 2/3)"
+    );
+    assert_eq!(
+        counts_with("unsafe_fns/free_fn.rs", &["--edition=2024"]),
+        "0/4 (with unsafe fn bodies: 2/4, performing unsafe operations: 1/4)"
+    );
+}
+
+#[test]
+fn unsafe_impl_and_trait_inventory() {
+    let path = "tests/fixtures/inventory/unsafe_items.rs";
+    let stdout = rustalyzer(&[path]);
+    let listing: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix(&format!("  {}:", path)))
+        .collect();
+    assert_eq!(
+        listing,
+        [
+            "5:1: unsafe impl<T: Send> Send for Handle<T>",
+            "6:1: unsafe impl<T> Sync for Handle<T> where T: Sync + 'static",
+            "10:1: unsafe impl GlobalAlloc for Arena",
+            "18:5: unsafe trait Zeroable: Sized",
+            "20:5: unsafe trait RawStorage<'a, T: ?Sized>",
+            "27:5: unsafe impl Send for Wrapper",
+        ]
+    );
+    assert!(stdout.ends_with("unsafe impls: 4, unsafe traits: 2\n"));
+}
+
+#[test]
+fn ffi_report() {
+    let path = "tests/fixtures/ffi/wrapper.rs";
+    let stdout = rustalyzer(&["--ffi", path]);
+    let report: Vec<String> = stdout
+        .lines()
+        .filter(|line| line.starts_with(' ') && line.trim_start().starts_with(path))
+        .map(|line| line.replacen(&format!("{}:", path), "", 1))
+        .collect();
+    assert_eq!(
+        report,
+        [
+            "  4:1: extern \"C\" block linking `z`",
+            "    5:5: import `deflateInit` (C): fn deflateInit(strm: *mut Stream, level: c_int) -> c_int",
+            "    7:5: import `zlibVersion` (C): fn zlib_version() -> *const c_char",
+            "    8:5: import `z_errno` (C): static mut z_errno: c_int",
+            "  11:1: unsafe extern \"system\" block",
+            "    12:5: import `GetLastError` (system): fn GetLastError() -> u32",
+            "  21:5: export `rz_version` (C): extern \"C\" fn rz_version() -> *const c_char",
+            "  26:5: export `rz_init` (C): unsafe extern \"C\" fn init(strm: *mut Stream) -> c_int",
+            "  31:5: export `RZ_ABI_VERSION` (C): static RZ_ABI_VERSION: u32",
+        ]
+    );
+    assert!(stdout.ends_with("ffi imports: 4, ffi exports: 3\n"));
+}
+
+#[test]
+fn unsafe_operation_classification() {
+    let stdout = rustalyzer(&["tests/fixtures/ops/operations.rs"]);
+    let ops = stdout.lines().nth(1).unwrap();
+    assert_eq!(
+        ops,
+        "  unsafe operations: 10 (raw pointer deref 3, unsafe fn call 3, \
+         unsafe trait method call 1, static mut access 1, union field read 1, asm! 1)"
+    );
+}
+
+#[test]
+fn statements_performing_unsafe_operations() {
+    assert_eq!(
+        counts("ops/benign_block.rs"),
+        "7/9 (unsafe blocks only: 7/9, performing unsafe operations: 2/9)"
+    );
+}
+
+#[test]
+fn calls_resolved_across_crate_files() {
+    let stdout = rustalyzer(&[
+        "tests/fixtures/resolve/src/lib.rs",
+        "tests/fixtures/resolve/src/ffi.rs",
+        "tests/fixtures/resolve/src/util/mod.rs",
+        "tests/fixtures/resolve/src/util/raw.rs",
+    ]);
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "tests/fixtures/resolve/src/lib.rs: 8/9 \
+         (unsafe blocks only: 8/9, performing unsafe operations: 5/9)"
+    );
+    assert_eq!(
+        lines.next().unwrap(),
+        "  unsafe operations: 4 (unsafe fn call 4); unresolved calls: 2"
+    );
+}
+
+#[test]
+fn std_unsafe_api_catalog() {
+    let stdout = rustalyzer(&["tests/fixtures/catalog/std_apis.rs"]);
+    assert_eq!(
+        stdout.lines().nth(1).unwrap(),
+        "  unsafe operations: 11 (raw pointer deref 1, unsafe fn call 10); unresolved calls: 11"
+    );
+}
+
+#[test]
+fn catalog_extended_by_config() {
+    let stdout = rustalyzer(&[
+        "--config",
+        "tests/fixtures/catalog/rustalyzer.toml",
+        "tests/fixtures/catalog/std_apis.rs",
+    ]);
+    assert_eq!(
+        stdout.lines().nth(1).unwrap(),
+        "  unsafe operations: 14 (raw pointer deref 1, unsafe fn call 13); unresolved calls: 8"
+    );
+}
+
+#[test]
+fn invalid_config() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustalyzer"))
+        .args(["--config", "tests/fixtures/catalog/std_apis.rs", "a.rs"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: tests/fixtures/catalog/std_apis.rs: line 1: "));
+}
+
+// Each warning as its header, its location and its primary label.
+fn warnings(stdout: &str) -> Vec<String> {
+    let lines: Vec<&str> = stdout.lines().collect();
+    (0..lines.len())
+        .filter(|&i| lines[i].starts_with("warning: "))
+        .map(|i| {
+            let label = lines[i..]
+                .iter()
+                .take_while(|line| !line.is_empty())
+                .find(|line| line.contains('^'))
+                .unwrap();
+            let label = label.split('|').nth(1).unwrap().trim();
+            format!("{} @ {} {}", lines[i], lines[i + 1].trim(), label)
+        })
+        .collect()
+}
+
+#[test]
+fn unused_unsafe_report() {
+    let stdout = rustalyzer(&["--unused-unsafe", "tests/fixtures/unused/blocks.rs"]);
+    assert_eq!(
+        warnings(&stdout),
+        [
+            "warning: unnecessary `unsafe` block @ --> blocks.rs:4:5 ^^^^^^ no unsafe operations",
+            "warning: unnecessary `unsafe` block @ --> blocks.rs:14:9 ^^^^^^ already inside an `unsafe` block",
+            "warning: `unsafe fn` performs no unsafe operations @ --> blocks.rs:19:5 ^^^^^^ no unsafe operations in its body",
+            "warning: unnecessary `unsafe` block @ --> blocks.rs:24:5 ^^^^^^ already inside an `unsafe` fn",
+        ]
+    );
+    // Nested blocks point at the `unsafe` they are already inside.
+    assert!(stdout.contains(
+        "12 |     unsafe {\n   |     ------ because it's nested under this `unsafe` block\n...\n"
+    ));
+    assert!(stdout.ends_with("unused unsafe blocks: 3, unused unsafe fns: 1\n"));
+    // `GetLastError` is an unsafe foreign fn, not a tuple struct.
+    assert_eq!(
+        counts("unused/blocks.rs"),
+        "12/17 (unsafe blocks only: 9/17, performing unsafe operations: 10/17)"
+    );
+}
+
+#[test]
+fn unused_unsafe_report_edition_2024() {
+    let stdout = rustalyzer(&[
+        "--unused-unsafe",
+        "--edition",
+        "2024",
+        "tests/fixtures/unused/blocks.rs",
+    ]);
+    assert_eq!(warnings(&stdout).len(), 3);
+    assert!(stdout.ends_with("unused unsafe blocks: 2, unused unsafe fns: 1\n"));
+}
+
+#[test]
+fn std_macro_arguments_visited() {
+    let stdout = rustalyzer(&["tests/fixtures/macros/invocations.rs"]);
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "tests/fixtures/macros/invocations.rs: 6/10 \
+         (unsafe blocks only: 6/10, performing unsafe operations: 9/10)"
+    );
+    assert_eq!(
+        lines.next().unwrap(),
+        "  unsafe operations: 6 (raw pointer deref 3, static mut access 3)"
+    );
+}
+
+#[test]
+fn user_macros_from_config() {
+    assert_eq!(
+        counts_with(
+            "macros/invocations.rs",
+            &["--config", "tests/fixtures/macros/rustalyzer.toml"]
+        ),
+        "7/13 (unsafe blocks only: 7/13, performing unsafe operations: 12/13)"
+    );
+}
+
+#[test]
+fn local_macro_rules_expanded() {
+    let stdout = rustalyzer(&["tests/fixtures/macro_rules/expansion.rs"]);
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "tests/fixtures/macro_rules/expansion.rs: 6/18 \
+         (unsafe blocks only: 6/18, performing unsafe operations: 14/18)"
+    );
+    assert_eq!(
+        lines.next().unwrap(),
+        "  unsafe operations: 6 (raw pointer deref 5, unsafe fn call 1); unexpanded macros: 1"
+    );
+}
+
+#[test]
+fn recursive_macro_rules_expanded_to_a_depth() {
+    let stdout = rustalyzer(&["tests/fixtures/macro_rules/recursive.rs"]);
+    assert_eq!(
+        stdout.lines().nth(1).unwrap(),
+        "  unsafe operations: 2 (raw pointer deref 2); unexpanded macros: 1"
+    );
+}
+
+#[test]
+fn macros_that_emit_unsafe() {
+    let stdout = rustalyzer(&[
+        "tests/fixtures/macro_rules/expansion.rs",
+        "tests/fixtures/macro_rules/unexpanded.rs",
+    ]);
+    let section: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "macros that emit unsafe:")
+        .collect();
+    assert_eq!(
+        section,
+        [
+            "macros that emit unsafe:",
+            "  tests/fixtures/macro_rules/expansion.rs:10:1: \
+             macro_rules! impl_ffi_getters (1 `unsafe` token, 1 invocation)",
+            "  tests/fixtures/macro_rules/expansion.rs:27:1: \
+             macro_rules! read_raw (1 `unsafe` token, 1 invocation)",
+            "  tests/fixtures/macro_rules/expansion.rs:41:1: \
+             macro_rules! call (1 `unsafe` token, 1 invocation)",
+            "  tests/fixtures/macro_rules/unexpanded.rs:1:1: \
+             macro_rules! zeroed (1 `unsafe` token, 2 invocations)",
+        ]
+    );
+}
+
+fn analysed_files(stdout: &str) -> Vec<&str> {
+    stdout
+        .lines()
+        .filter(|line| !line.starts_with(' ') && !line.starts_with("total: "))
+        .filter_map(|line| line.split(": ").next())
+        .filter(|file| file.ends_with(".rs"))
+        .collect()
+}
+
+#[test]
+fn directory_walk_honours_ignore_files() {
+    let stdout = rustalyzer(&["tests/fixtures/walk"]);
+    assert_eq!(
+        analysed_files(&stdout),
+        [
+            "tests/fixtures/walk/src/keep.generated.rs",
+            "tests/fixtures/walk/src/lib.rs",
+            "tests/fixtures/walk/src/nested/kept.rs",
+            "tests/fixtures/walk/src/nested/mod.rs",
+            "tests/fixtures/walk/src/util/mod.rs",
+            "tests/fixtures/walk/tests/it.rs",
+        ]
+    );
+}
+
+#[test]
+fn directory_walk_filters() {
+    let stdout = rustalyzer(&[
+        "--exclude",
+        "tests/",
+        "--include=src/nested",
+        "--include",
+        "src/util/*.rs",
+        "tests/fixtures/walk",
+        "tests/fixtures/walk/vendor/dep.rs",
+    ]);
+    assert_eq!(
+        analysed_files(&stdout),
+        [
+            "tests/fixtures/walk/src/nested/kept.rs",
+            "tests/fixtures/walk/src/nested/mod.rs",
+            "tests/fixtures/walk/src/util/mod.rs",
+            "tests/fixtures/walk/vendor/dep.rs",
+        ]
+    );
+}
+
+#[test]
+fn unreadable_inputs_are_skipped() {
+    let (stdout, stderr) = rustalyzer_failing(&[
+        "tests/fixtures/missing.rs",
+        "tests/fixtures/encoding/latin1.rs",
+        "tests/fixtures/walk/src/lib.rs",
+    ]);
+    assert_eq!(analysed_files(&stdout), ["tests/fixtures/walk/src/lib.rs"]);
+    assert_eq!(
+        stderr.lines().collect::<Vec<_>>(),
+        [
+            "Unable to read tests/fixtures/missing.rs: No such file or directory (os error 2)",
+            "Unable to decode tests/fixtures/encoding/latin1.rs: invalid utf-8 sequence of 1 bytes from index 6",
+        ]
+    );
+}
+
+#[test]
+fn unreadable_files_in_directories_are_skipped() {
+    let (stdout, stderr) = rustalyzer_failing(&[
+        "tests/fixtures/unreadable",
+        "tests/fixtures/walk/src/lib.rs",
+    ]);
+    assert_eq!(
+        analysed_files(&stdout),
+        [
+            "tests/fixtures/unreadable/ok.rs",
+            "tests/fixtures/walk/src/lib.rs",
+        ]
+    );
+    assert!(stdout.contains("\ntotal: 2/4 "));
+    assert_eq!(
+        stderr.lines().collect::<Vec<_>>(),
+        [
+            "Unable to read tests/fixtures/unreadable/broken.rs: No such file or directory (os error 2)",
+            "Unable to decode tests/fixtures/unreadable/latin1.rs: invalid utf-8 sequence of 1 bytes from index 6",
+        ]
+    );
+}
+
+#[test]
+fn unreadable_directories_are_skipped() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("unreadable_directories");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("locked")).unwrap();
+    fs::write(dir.join("a.rs"), "fn a() {}\n").unwrap();
+    fs::write(dir.join("locked/b.rs"), "fn b() {}\n").unwrap();
+    fs::write(dir.join("z.rs"), "fn z() {}\n").unwrap();
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+    // Permissions do not keep the superuser out.
+    if fs::read_dir(dir.join("locked")).is_ok() {
+        return;
+    }
+    let input = dir.to_str().unwrap();
+    let (stdout, stderr) = rustalyzer_failing(&[input]);
+    fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(
+        analysed_files(&stdout),
+        [format!("{}/a.rs", input), format!("{}/z.rs", input)]
+    );
+    assert_eq!(
+        stderr.trim_end(),
+        format!(
+            "Unable to read {}/locked: Permission denied (os error 13)",
+            input
+        )
+    );
+}
+
+#[test]
+fn items_that_do_not_parse_are_skipped() {
+    let (stdout, stderr) = rustalyzer_failing(&["tests/fixtures/recover/nightly.rs"]);
+    assert!(stdout.starts_with("tests/fixtures/recover/nightly.rs: 3/7 "));
+    let headers: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("error: "))
+        .collect();
+    assert_eq!(
+        headers,
+        [
+            "error: Syn unable to parse item, skipping lines 8-11",
+            "error: Syn unable to parse item, skipping line 25",
+        ]
+    );
+    assert!(stderr.contains("  --> nightly.rs:10:5\n"), "{}", stderr);
+
+    // An item macro after a block starts an item of its own.
+    let (stdout, stderr) = rustalyzer_failing(&["tests/fixtures/recover/macro_items.rs"]);
+    assert!(stdout.starts_with("tests/fixtures/recover/macro_items.rs: 1/2 "));
+    assert!(stderr.contains("error: Syn unable to parse item, skipping lines 3-6\n"));
+
+    // Items that all parse on their own do not make a file that parses.
+    let (stdout, stderr) = rustalyzer_failing(&[
+        "tests/fixtures/recover/misplaced_attr.rs",
+        "tests/fixtures/walk/src/lib.rs",
+    ]);
+    assert_eq!(analysed_files(&stdout), ["tests/fixtures/walk/src/lib.rs"]);
+    assert!(
+        stderr.contains("error: Syn unable to parse file\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn workspace_targets_follow_module_tree() {
+    // `tool` declares a module whose file is missing.
+    let (stdout, _) = rustalyzer_failing(&["tests/fixtures/cargo/Cargo.toml"]);
+    let outline: Vec<&str> = stdout
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("unsafe operations"))
+        .map(|line| line.split(": ").next().unwrap())
+        .collect();
+    assert_eq!(
+        outline,
+        [
+            "package `core-io`",
+            "  lib `core_io`",
+            "    tests/fixtures/cargo/crates/core/src/lib.rs",
+            "    tests/fixtures/cargo/crates/core/src/ffi/mod.rs",
+            "    tests/fixtures/cargo/crates/core/src/ffi/raw.rs",
+            "    tests/fixtures/cargo/crates/core/src/platform/unix.rs",
+            "    tests/fixtures/cargo/crates/core/src/platform/detail.rs",
+            "    tests/fixtures/cargo/crates/core/src/inline/nested.rs",
+            "  example `demo`",
+            "    tests/fixtures/cargo/crates/core/examples/demo/main.rs",
+            "  test `smoke`",
+            "    tests/fixtures/cargo/crates/core/tests/smoke.rs",
+            "  build script `build-script-build`",
+            "    tests/fixtures/cargo/crates/core/build.rs",
+            "package `tool`",
+            "  bin `tool-cli`",
+            "    tests/fixtures/cargo/tool/cli.rs",
+            "total",
+            "by role:",
+            "  production",
+            "  test",
+            "  example",
+            "  build script",
+        ]
+    );
+    assert!(stdout.contains(
+        "\n  lib `core_io`: 2/4 (unsafe blocks only: 1/4, performing unsafe operations: 3/4)\n"
+    ));
+    // The 2024 edition of `tool` only counts explicit unsafe blocks.
+    assert!(stdout.contains(
+        "\n  bin `tool-cli`: 1/4 (with unsafe fn bodies: 1/4, performing unsafe operations: 2/4)\n"
+    ));
+}
+
+#[test]
+fn modules_declared_in_macros_are_followed() {
+    let files = |flags: &[&str]| {
+        let mut args = flags.to_vec();
+        args.push("tests/fixtures/macro_mods/Cargo.toml");
+        let stdout = rustalyzer(&args);
+        stdout
+            .lines()
+            .filter(|line| line.starts_with("    tests/"))
+            .map(|line| line.trim().split(": ").next().unwrap().to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        files(&[]),
+        [
+            "tests/fixtures/macro_mods/src/lib.rs",
+            "tests/fixtures/macro_mods/src/inner.rs",
+            "tests/fixtures/macro_mods/src/unix.rs",
+            "tests/fixtures/macro_mods/src/windows.rs",
+            "tests/fixtures/macro_mods/src/other.rs",
+        ]
+    );
+    // Only the first `cfg_if!` branch that applies is compiled.
+    assert_eq!(
+        files(&["--target-os", "linux"]),
+        [
+            "tests/fixtures/macro_mods/src/lib.rs",
+            "tests/fixtures/macro_mods/src/inner.rs",
+            "tests/fixtures/macro_mods/src/unix.rs",
+        ]
+    );
+    assert_eq!(
+        files(&["--target-os", "windows"]),
+        [
+            "tests/fixtures/macro_mods/src/lib.rs",
+            "tests/fixtures/macro_mods/src/inner.rs",
+            "tests/fixtures/macro_mods/src/windows.rs",
+        ]
+    );
+}
+
+#[test]
+fn cfg_selects_compiled_code() {
+    let files = |flags: &[&str]| {
+        let mut args = flags.to_vec();
+        args.push("tests/fixtures/cfg/Cargo.toml");
+        let stdout = rustalyzer(&args);
+        stdout
+            .lines()
+            .filter(|line| line.starts_with("    tests/"))
+            .map(|line| line.trim().to_string())
+            .collect::<Vec<String>>()
+    };
+    // Without a cfg set, every predicate is unknown and nothing is skipped.
+    assert_eq!(
+        files(&[]),
+        [
+            "tests/fixtures/cfg/src/lib.rs: 3/10 (unsafe blocks only: 3/10, performing unsafe operations: 6/10)",
+            "tests/fixtures/cfg/src/unix.rs: 1/2 (unsafe blocks only: 1/2, performing unsafe operations: 2/2)",
+            "tests/fixtures/cfg/src/windows.rs: 1/3 (unsafe blocks only: 1/3, performing unsafe operations: 2/3)",
+        ]
+    );
+    // Default features only, so neither `simd` nor `fast`, and no `test`.
+    assert_eq!(
+        files(&["--target-os", "linux"]),
+        [
+            "tests/fixtures/cfg/src/lib.rs: 0/3 (unsafe blocks only: 0/3, performing unsafe operations: 0/3)",
+            "tests/fixtures/cfg/src/unix.rs: 1/2 (unsafe blocks only: 1/2, performing unsafe operations: 2/2)",
+        ]
+    );
+    // Naming the target family or OS as a cfg selects the target too.
+    for flags in [["--cfg", "unix"], ["--cfg", "target_os=linux"]] {
+        assert_eq!(
+            files(&flags),
+            [
+                "tests/fixtures/cfg/src/lib.rs: 0/3 (unsafe blocks only: 0/3, performing unsafe operations: 0/3)",
+                "tests/fixtures/cfg/src/unix.rs: 1/2 (unsafe blocks only: 1/2, performing unsafe operations: 2/2)",
+            ]
+        );
+    }
+    // `simd` enables `fast`, and without `std` the `cfg_attr` adds no `cfg`.
+    assert_eq!(
+        files(&["--features=simd", "--target-os=windows", "--no-default-features"]),
+        [
+            "tests/fixtures/cfg/src/lib.rs: 3/7 (unsafe blocks only: 3/7, performing unsafe operations: 6/7)",
+            "tests/fixtures/cfg/src/windows.rs: 1/3 (unsafe blocks only: 1/3, performing unsafe operations: 2/3)",
+        ]
+    );
+}
+
+#[test]
+fn unsafe_by_cfg() {
+    let stdout = rustalyzer(&["--by-cfg", "tests/fixtures/cfg/Cargo.toml"]);
+    let section: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "by cfg:")
+        .filter(|line| !line.starts_with("    "))
+        .map(|line| line.split(" (").next().unwrap())
+        .collect();
+    assert_eq!(
+        section,
+        [
+            "by cfg:",
+            "  all(feature = \"std\", target_os = \"linux\"): 0/1",
+            "  feature = \"fast\": 1/2",
+            "  feature = \"simd\": 1/2",
+            "  not(feature = \"simd\"): 0/2",
+            "  test: 1/3",
+            "  unix: 1/2",
+            "  windows: 1/3",
+        ]
+    );
+}
+
+#[test]
+fn unsafe_by_role() {
+    let section = |flags: &[&str]| {
+        let mut args = flags.to_vec();
+        args.push("tests/fixtures/roles/Cargo.toml");
+        let stdout = rustalyzer(&args);
+        stdout
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("unsafe operations"))
+            .skip_while(|line| *line != "by role:")
+            .map(|line| line.split(" (unsafe").next().unwrap().to_string())
+            .collect::<Vec<String>>()
+    };
+    // `#[test]` functions and `#[cfg(test)]` modules count as test code.
+    assert_eq!(
+        section(&[]),
+        [
+            "by role:",
+            "  production: 1/2",
+            "  test: 2/7",
+            "  bench: 1/4",
+            "  example: 0/2",
+            "  build script: 0/1",
+        ]
+    );
+    // Targets other than the library and binaries are not even read.
+    assert_eq!(
+        section(&["--production-only"]),
+        ["by role:", "  production: 1/2", "  test: 2/5",]
+    );
+    let stdout = rustalyzer(&["--production-only", "tests/fixtures/roles/Cargo.toml"]);
+    assert!(stdout.contains("\ntotal: 1/2 "));
+    assert!(stdout.contains("  test: 2/5 (unsafe blocks only: 2/5, performing unsafe operations: 4/5) (excluded from total)\n"));
+    assert!(!stdout.contains("bench `bench`"));
+}
+
+#[test]
+fn dependency_tree_from_lockfile() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustalyzer"))
+        .args(["--deps", "tests/fixtures/deps/Cargo.toml"])
+        .env("CARGO_HOME", "tests/fixtures/deps/cargo-home")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run rustalyzer");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let tree: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "dependencies:")
+        .map(|line| line.split(" (unsafe").next().unwrap())
+        .collect();
+    assert_eq!(
+        tree,
+        [
+            "dependencies:",
+            "  app 0.1.0: 1/2",
+            "  ├── gitdep 0.5.0 (git): 0/1",
+            "  ├── helper 0.1.0 (path): 0/1",
+            "  │   └── registered 0.3.0 (registry): 2/3",
+            "  ├── remote 2.1.0 (registry): sources not available locally",
+            "  │   └── registered 0.3.0 (registry) (*)",
+            "  └── vendored 1.0.0 (vendored): 2/4",
+        ]
+    );
+    // Dependencies are left out of the package listing and the total.
+    assert!(!stdout.contains("package `registered`"));
+    assert!(stdout.contains("\ntotal: 1/2 "));
+}
+
+#[test]
+fn archives_are_analysed_in_memory() {
+    let (stdout, _) = rustalyzer_failing(&[
+        "tests/fixtures/archive/demo-0.1.0.crate",
+        "tests/fixtures/archive/nested.tar.gz",
+        "tests/fixtures/archive/truncated.crate",
+    ]);
+    let outline: Vec<&str> = stdout
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("unsafe operations"))
+        .map(|line| line.split(" (").next().unwrap())
+        .collect();
+    assert_eq!(
+        outline,
+        [
+            "package `demo`: 3/6",
+            "  lib `demo`: 3/5",
+            "    tests/fixtures/archive/demo-0.1.0.crate/demo-0.1.0/src/lib.rs: 2/4",
+            "    tests/fixtures/archive/demo-0.1.0.crate/demo-0.1.0/src/util/mod.rs: 1/1",
+            "  bin `tool`: 0/1",
+            "    tests/fixtures/archive/demo-0.1.0.crate/demo-0.1.0/src/bin/tool.rs: 0/1",
+            "package `nested`: 1/3",
+            "  lib `nested`: 1/3",
+            "    tests/fixtures/archive/nested.tar.gz/deeply/nested/directory/structure/that/goes/on/for/quite/a/while/before/reaching/the/package/src/lib.rs: 0/0",
+            "    tests/fixtures/archive/nested.tar.gz/deeply/nested/directory/structure/that/goes/on/for/quite/a/while/before/reaching/the/package/src/a_module_with_a_rather_long_name_to_push_the_path_over_one_hundred_bytes/mod.rs: 1/3",
+            "total: 4/9",
+        ]
+    );
+    // The features of the archived manifest are honoured.
+    let stdout = rustalyzer(&[
+        "--no-default-features",
+        "tests/fixtures/archive/demo-0.1.0.crate",
+    ]);
+    assert!(stdout.starts_with("package `demo`: 2/4 "));
+}
+
+#[test]
+fn diff_between_versions() {
+    let stdout = rustalyzer(&["diff", "tests/fixtures/diff/old", "tests/fixtures/diff/new"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let changes: Vec<String> = lines
+        .windows(5)
+        .filter(|window| window[0].contains(": unsafe "))
+        .map(|window| {
+            let label = window[4].split('|').nth(1).unwrap().trim();
+            let label = label.trim_start_matches('^').trim();
+            format!("{} @ {} {}", window[0], window[1].trim(), label)
+        })
+        .collect();
+    // Reformatting and moving `util` to `util/mod.rs` are not changes.
+    assert_eq!(
+        changes,
+        [
+            "removed: unsafe impl `pair::<Handle as Send>` @ --> lib.rs:5:1 not in the new version",
+            "added: unsafe impl `pair::<Handle as Sync>` @ --> lib.rs:5:1 not in the old version",
+            "added: unsafe block in `pair::Handle::set` @ --> lib.rs:20:9 not in the old version",
+            "removed: unsafe block in `pair::repeat` @ --> lib.rs:22:10 not in the new version",
+            "modified: unsafe block in `pair::second` @ --> lib.rs:25:5 changed since lib.rs:18:5",
+        ]
+    );
+    // The old code of a modified region is shown beneath the new.
+    assert!(stdout.contains(concat!(
+        "   |\n",
+        "  ::: lib.rs:18:5\n",
+        "   |\n",
+        "18 |     unsafe { util::read(bytes.as_ptr()) }\n",
+        "   |     ------------------------------------- the old version\n",
+    )));
+    assert!(stdout.ends_with("unsafe added: 2, removed: 2, modified: 1, unchanged: 3\n"));
+}
+
+#[test]
+fn diagnostics_render_multi_line_spans() {
+    let stdout = rustalyzer(&[
+        "diff",
+        "tests/fixtures/render/old",
+        "tests/fixtures/render/new",
+    ]);
+    // The tab and the wide characters before the block are accounted for,
+    // and the middle of the long block is left out.
+    assert!(stdout.starts_with(concat!(
+        "\n",
+        "modified: unsafe block in `render::f`\n",
+        " --> lib.rs:2:17\n",
+        "  |\n",
+        "2 |       let s = \"日本語\"; unsafe {\n",
+        "  |  _______________________^\n",
+        "3 | |         let a = *p;\n",
+        "...\n",
+        "8 | |         a + b + c + d + e\n",
+        "9 | |     }\n",
+        "  | |_____^ changed since lib.rs:2:5\n",
+        "  |\n",
+        " ::: lib.rs:2:5\n",
+        "  |\n",
+        "2 |     unsafe { *p }\n",
+        "  |     ------------- the old version\n",
+    )));
+
+    // A span starting at the margin is continued beneath its first line.
+    let (_, stderr) = rustalyzer_failing(&["tests/fixtures/recover/pattern.rs"]);
+    assert!(stderr.starts_with(concat!(
+        "\n",
+        "error: Syn unable to parse item, skipping lines 1-3\n",
+        " --> pattern.rs:1:12\n",
+        "  |\n",
+        "1 | / fn f(x: u8 @ y) {\n",
+        "  | |            ^ expected `,`\n",
+        "2 | |     let a = 1;\n",
+        "3 | | }\n",
+        "  | |_^ item skipped\n",
+    )));
+
+    let args = [
+        "diff",
+        "--color=always",
+        "tests/fixtures/render/old",
+        "tests/fixtures/render/new",
+    ];
+    assert!(rustalyzer(&args).contains("\x1b[1;33mmodified\x1b[0m"));
+    let args = [
+        "diff",
+        "--color",
+        "never",
+        "tests/fixtures/render/old",
+        "tests/fixtures/render/new",
+    ];
+    assert!(!rustalyzer(&args).contains('\x1b'));
+}
+
+#[test]
+fn unsafe_by_function() {
+    let stdout = rustalyzer(&["--by-function", "tests/fixtures/functions/Cargo.toml"]);
+    let functions: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| *line != "by function:")
+        .skip(1)
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            "  tests/fixtures/functions/src/raw.rs:17:5: fn functions::raw::<Buffer as Clone>::clone: 2/6 (2 unsafe blocks)",
+            "  tests/fixtures/functions/src/lib.rs:5:5: fn functions::sum: 1/4 (1 unsafe block)",
+            "  tests/fixtures/functions/src/lib.rs:16:5: fn functions::outer::inner: 1/2 (1 unsafe block)",
+            "  tests/fixtures/functions/src/raw.rs:7:9: fn functions::raw::Buffer::first: 1/2 (1 unsafe block)",
+            "  tests/fixtures/functions/src/raw.rs:11:16: unsafe fn functions::raw::Buffer::set_len: 1/1 (0 unsafe blocks)",
+            "  tests/fixtures/functions/src/lib.rs:15:5: fn functions::outer: 0/2 (0 unsafe blocks)",
+            "  tests/fixtures/functions/src/lib.rs:22:5: fn functions::first: 0/2 (0 unsafe blocks)",
+        ]
+    );
+    assert!(!rustalyzer(&["tests/fixtures/functions/Cargo.toml"]).contains("by function:"));
+}
+
+fn rustalyzer_stdin(args: &[&str], stdin: &str) -> (Option<i32>, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rustalyzer"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run rustalyzer");
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.as_bytes()).unwrap();
+    drop(input);
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn source_from_stdin() {
+    let src = "fn main() {\n    let x = 1;\n    unsafe { *(&x as *const i32) };\n}\n";
+    let (status, stdout, _) = rustalyzer_stdin(&["-"], src);
+    assert_eq!(status, Some(0));
+    assert!(stdout.starts_with("<stdin>: 1/3 "));
+
+    // The name stands in for the file, including when locating its package.
+    let (status, stdout, _) = rustalyzer_stdin(
+        &["--stdin-filename", "tests/fixtures/cfg/src/lib.rs", "-"],
+        src,
+    );
+    assert_eq!(status, Some(0));
+    assert!(stdout.starts_with("tests/fixtures/cfg/src/lib.rs: 1/3 "));
+
+    let (status, _, stderr) = rustalyzer_stdin(
+        &["--stdin-filename=src/buffer.rs", "-"],
+        "fn main() {\n    let = 1;\n}\n",
+    );
+    assert_eq!(status, Some(1));
+    assert!(stderr.contains(" --> buffer.rs:2:9\n"), "{}", stderr);
+}
+
+#[test]
+fn argument_files_list_inputs() {
+    let stdout = rustalyzer(&["@tests/fixtures/args/inputs.txt"]);
+    assert_eq!(
+        analysed_files(&stdout),
+        [
+            "tests/fixtures/unsafe_fns/free_fn.rs",
+            "tests/fixtures/walk/src/lib.rs",
+        ]
+    );
+}
@@ -0,0 +1,3 @@
+pub unsafe fn get(p: *const u32) -> u32 {
+    *p
+}
@@ -0,0 +1,11 @@
+mod ffi;
+#[path = "platform/unix.rs"]
+mod sys;
+
+mod inline {
+    mod nested;
+}
+
+pub fn read(p: *const u8) -> u8 {
+    unsafe { *p }
+}
@@ -0,0 +1,3 @@
+pub fn orphan(p: *const u8) -> u8 {
+    unsafe { *p }
+}
@@ -0,0 +1,3 @@
+pub fn page_size() -> usize {
+    4096
+}
@@ -0,0 +1,4 @@
+#[test]
+fn smoke() {
+    assert_eq!(core_io::read(&1), 1);
+}
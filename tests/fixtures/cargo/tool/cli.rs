@@ -0,0 +1,9 @@
+mod missing;
+
+fn main() {
+    let x = 0u8;
+    let p = &x as *const u8;
+    unsafe {
+        let _ = *p;
+    }
+}
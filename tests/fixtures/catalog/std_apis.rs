@@ -0,0 +1,34 @@
+use std::mem;
+use std::ptr::{self, NonNull};
+use std::slice::from_raw_parts;
+
+use libc::{c_void, malloc};
+
+pub fn catalog(v: &mut Vec<u8>, p: *mut u8, bytes: &[u8]) -> u8 {
+    unsafe {
+        v.set_len(0);
+        let first = *bytes.get_unchecked(0);
+        let s = std::str::from_utf8_unchecked(bytes);
+        let x: u32 = mem::transmute(1.0f32);
+        ptr::write(p, first);
+        let _ = from_raw_parts(p, 1);
+        let q = p.add(1);
+        q.write(2);
+        let nn = NonNull::new_unchecked(p);
+        let raw = malloc(4) as *mut c_void;
+        libc::free(raw);
+        Vec::set_len(v, 1);
+        v.lock_unchecked();
+        let n = s.len() + x as usize;
+        first + n as u8 + *nn.as_ptr()
+    }
+}
+
+pub fn safe_lookalikes(v: &mut Vec<u8>, data: &[u8]) -> usize {
+    unsafe {
+        let sum = v.iter().map(|b| *b as usize).sum::<usize>();
+        let mut copy = data.to_vec();
+        copy.add(1);
+        sum + copy.len()
+    }
+}
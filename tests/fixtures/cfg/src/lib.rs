@@ -0,0 +1,30 @@
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(feature = "simd")]
+pub fn sum(p: *const u32) -> u32 {
+    unsafe { *p }
+}
+
+#[cfg(not(feature = "simd"))]
+pub fn sum(p: *const u32) -> u32 {
+    let _ = p;
+    0
+}
+
+pub fn copy(src: *const u8, dst: *mut u8) {
+    #[cfg(feature = "fast")]
+    unsafe {
+        *dst = *src;
+    }
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    let _ = (src, dst);
+}
+
+#[cfg_attr(feature = "std", cfg(test))]
+fn only_in_tests() {
+    let x = 1;
+    let _ = unsafe { *(&x as *const i32) };
+}
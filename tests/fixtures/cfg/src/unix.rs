@@ -0,0 +1,7 @@
+pub fn page_size() -> usize {
+    unsafe { sysconf(30) as usize }
+}
+
+extern "C" {
+    fn sysconf(name: i32) -> i64;
+}
@@ -0,0 +1,4 @@
+pub fn page_size() -> usize {
+    let p = &4096usize as *const usize;
+    unsafe { *p }
+}
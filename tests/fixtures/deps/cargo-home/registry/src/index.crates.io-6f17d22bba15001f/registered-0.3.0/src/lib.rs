@@ -0,0 +1,5 @@
+mod raw;
+
+pub fn get(v: &[u32], i: usize) -> u32 {
+    unsafe { raw::get(v, i) }
+}
@@ -0,0 +1,3 @@
+pub unsafe fn get(v: &[u32], i: usize) -> u32 {
+    *v.get_unchecked(i)
+}
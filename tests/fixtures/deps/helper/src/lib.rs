@@ -0,0 +1,3 @@
+pub fn len(bytes: &[u8]) -> usize {
+    bytes.len()
+}
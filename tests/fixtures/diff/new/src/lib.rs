@@ -0,0 +1,30 @@
+mod util;
+
+pub struct Handle(*mut u8);
+
+unsafe impl Sync for Handle {}
+
+pub fn first(bytes: &[u8]) -> u8 {
+    // Reading the first byte is fine, the slice is never empty.
+    unsafe {
+        *bytes.as_ptr()
+    }
+}
+
+impl Handle {
+    pub fn get(&self) -> u8 {
+        unsafe { *self.0 }
+    }
+
+    pub fn set(&self, value: u8) {
+        unsafe { *self.0 = value }
+    }
+}
+
+pub fn second(bytes: &[u8]) -> u8 {
+    unsafe { util::read(bytes.as_ptr().add(1)) }
+}
+
+pub fn repeat(byte: &u8) -> Vec<u8> {
+    vec![*byte; 2]
+}
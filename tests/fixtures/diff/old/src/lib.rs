@@ -0,0 +1,23 @@
+mod util;
+
+pub struct Handle(*mut u8);
+
+unsafe impl Send for Handle {}
+
+pub fn first(bytes: &[u8]) -> u8 {
+    unsafe { *bytes.as_ptr() }
+}
+
+impl Handle {
+    pub fn get(&self) -> u8 {
+        unsafe { *self.0 }
+    }
+}
+
+pub fn second(bytes: &[u8]) -> u8 {
+    unsafe { util::read(bytes.as_ptr()) }
+}
+
+pub fn repeat(p: *const u8) -> Vec<u8> {
+    vec![unsafe { *p }; 2]
+}
@@ -0,0 +1,35 @@
+use std::os::raw::{c_char, c_int};
+
+#[link(name = "z", kind = "static")]
+extern "C" {
+    fn deflateInit(strm: *mut Stream, level: c_int) -> c_int;
+    #[link_name = "zlibVersion"]
+    fn zlib_version() -> *const c_char;
+    static mut z_errno: c_int;
+}
+
+unsafe extern "system" {
+    fn GetLastError() -> u32;
+}
+
+#[repr(C)]
+pub struct Stream {
+    _private: [u8; 0],
+}
+
+#[no_mangle]
+pub extern "C" fn rz_version() -> *const c_char {
+    unsafe { zlib_version() }
+}
+
+#[export_name = "rz_init"]
+pub unsafe extern "C" fn init(strm: *mut Stream) -> c_int {
+    deflateInit(strm, 6)
+}
+
+#[no_mangle]
+pub static RZ_ABI_VERSION: u32 = 1;
+
+pub extern "C" fn callback(x: c_int) -> c_int {
+    x
+}
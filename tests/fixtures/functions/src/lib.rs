@@ -0,0 +1,29 @@
+mod raw;
+
+pub use raw::Buffer;
+
+pub fn sum(ptrs: &[*const u32]) -> u32 {
+    // The closure's statements belong to `sum`.
+    ptrs.iter()
+        .map(|&p| {
+            let value = unsafe { *p };
+            value
+        })
+        .sum()
+}
+
+pub fn outer(p: *const u8) -> u8 {
+    fn inner(p: *const u8) -> u8 {
+        unsafe { *p }
+    }
+    inner(p)
+}
+
+pub fn first() -> u8 {
+    // The statements of the constant belong to no function.
+    const FIRST: u8 = {
+        let bytes = [1u8, 2];
+        unsafe { *bytes.as_ptr() }
+    };
+    FIRST
+}
@@ -0,0 +1,23 @@
+pub struct Buffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Buffer {
+    pub fn first(&self) -> u8 {
+        unsafe { *self.ptr }
+    }
+
+    pub unsafe fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
+}
+
+impl Clone for Buffer {
+    fn clone(&self) -> Buffer {
+        let ptr = self.ptr;
+        let len = self.len;
+        unsafe { std::ptr::read(&ptr) };
+        unsafe { Buffer { ptr, len } }
+    }
+}
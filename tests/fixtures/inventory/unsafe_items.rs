@@ -0,0 +1,28 @@
+use std::alloc::{GlobalAlloc, Layout};
+
+pub struct Handle<T>(*mut T);
+
+unsafe impl<T: Send> Send for Handle<T> {}
+unsafe impl<T> Sync for Handle<T> where T: Sync + 'static {}
+
+pub struct Arena;
+
+unsafe impl GlobalAlloc for Arena {
+    unsafe fn alloc(&self, _layout: Layout) -> *mut u8 {
+        std::ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
+
+pub unsafe trait Zeroable: Sized {}
+
+pub unsafe trait RawStorage<'a, T: ?Sized> {
+    fn as_ptr(&self) -> *const T;
+}
+
+mod nested {
+    pub struct Wrapper(pub *const u8);
+
+    unsafe impl Send for Wrapper {}
+}
@@ -0,0 +1,21 @@
+macro_rules! items {
+    ($($item:item)*) => {
+        $($item)*
+    };
+}
+
+items! {
+    mod inner;
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        mod unix;
+        pub use unix::read;
+    } else if #[cfg(windows)] {
+        mod windows;
+        pub use windows::read;
+    } else {
+        mod other;
+    }
+}
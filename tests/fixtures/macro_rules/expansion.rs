@@ -0,0 +1,59 @@
+pub struct Raw {
+    len: u32,
+    cap: u32,
+}
+
+pub struct Handle {
+    raw: *mut Raw,
+}
+
+macro_rules! impl_ffi_getters {
+    ($ty:ident { $($name:ident: $field:ident),* $(,)? }) => {
+        impl $ty {
+            $(
+                pub fn $name(&self) -> u32 {
+                    unsafe { (*self.raw).$field }
+                }
+            )*
+        }
+    };
+}
+
+impl_ffi_getters!(Handle {
+    len: len,
+    capacity: cap,
+});
+
+macro_rules! read_raw {
+    ($p:expr) => {
+        unsafe { *$p }
+    };
+}
+
+macro_rules! read_all {
+    () => {};
+    ($p:expr $(, $rest:expr)*) => {
+        let _ = read_raw!($p);
+        read_all!($($rest),*);
+    };
+}
+
+macro_rules! call {
+    ($($segment:ident)::+ ($($arg:expr),*)) => {
+        unsafe { $($segment)::+($($arg),*) }
+    };
+}
+
+macro_rules! forever {
+    ($x:expr) => {
+        forever!($x)
+    };
+}
+
+pub fn invocations(a: *const u32, b: *const u32) -> u32 {
+    read_all!(a, b);
+    let value = read_raw!(a);
+    let first = call!(std::ptr::read(b));
+    forever!(value);
+    value + first
+}
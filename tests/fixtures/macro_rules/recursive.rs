@@ -0,0 +1,18 @@
+macro_rules! deref_each {
+    () => {};
+    ($p:ident $($rest:ident)*) => {
+        unsafe { *$p };
+        deref_each!($($rest)*);
+    };
+}
+
+macro_rules! forever {
+    ($p:expr) => {
+        forever!($p)
+    };
+}
+
+pub fn touch(a: *const u8, b: *const u8) {
+    deref_each!(a b);
+    forever!(a);
+}
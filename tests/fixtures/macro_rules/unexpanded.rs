@@ -0,0 +1,18 @@
+macro_rules! zeroed {
+    ($ty:ty; $($len:literal)*) => {
+        unsafe { core::mem::zeroed::<[$ty; ${count($len)}]>() }
+    };
+}
+
+macro_rules! wrap {
+    ($e:expr) => {
+        Some($e)
+    };
+}
+
+pub fn buffers() -> ([u8; 4], [u16; 2]) {
+    let small = zeroed!(u8; 1 2 3 4);
+    let wide = vec![zeroed!(u16; 1 2)];
+    let _ = wrap!(small);
+    (small, wide[0])
+}
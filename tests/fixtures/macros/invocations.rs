@@ -0,0 +1,24 @@
+use std::cell::Cell;
+
+static mut LEVEL: u32 = 0;
+
+thread_local! {
+    static DEPTH: Cell<u32> = Cell::new(unsafe { LEVEL });
+}
+
+lazy_static! {
+    static ref LIMIT: u32 = unsafe { LEVEL * 2 };
+}
+
+pub fn arguments(p: *const u32) -> Vec<u32> {
+    println!("{} {level}", unsafe { *p }, level = unsafe { LEVEL });
+    assert_eq!(unsafe { *p }, 1, "unexpected value {}", 2);
+    vec![unsafe { *p }; 4]
+}
+
+pub fn user_macro(p: *mut u32) {
+    critical_section! {
+        let value = 1;
+        unsafe { *p = value };
+    }
+}
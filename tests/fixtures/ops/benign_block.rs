@@ -0,0 +1,13 @@
+fn checksum(data: &[u8]) -> u32 {
+    let p = data.as_ptr();
+    unsafe {
+        let mut sum = 0u32;
+        let mut i = 0;
+        while i < data.len() {
+            sum += data[i] as u32;
+            i += 1;
+        }
+        sum += *p as u32;
+        sum
+    }
+}
@@ -0,0 +1,60 @@
+use std::arch::asm;
+
+static mut COUNTER: u32 = 0;
+
+extern "C" {
+    fn abs(x: i32) -> i32;
+}
+
+union Bits {
+    float: f32,
+    int: u32,
+}
+
+struct Buffer {
+    data: *mut u8,
+    len: usize,
+}
+
+unsafe fn poke(p: *mut u8) {
+    *p = 0;
+}
+
+unsafe trait RawRead {
+    unsafe fn read_raw(&self) -> u8;
+}
+
+impl Buffer {
+    unsafe fn first(&self) -> u8 {
+        *self.data
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn run(buf: &Buffer, bits: Bits, reader: &dyn RawRead) -> u32 {
+    let local = [1u8, 2, 3];
+    let p = local.as_ptr();
+    let q = &local[0];
+    let mut bits = bits;
+    bits.int = 1;
+    unsafe {
+        let a = *p;
+        let b = *q;
+        poke(buf.data);
+        let c = buf.first();
+        let d = reader.read_raw();
+        COUNTER += 1;
+        let e = bits.float;
+        let f = abs(-1);
+        asm!("nop");
+        a as u32 + b as u32 + c as u32 + d as u32 + e as u32 + f as u32 + buf.len() as u32
+    }
+}
+
+fn shadowed() -> u8 {
+    let COUNTER = 5u8;
+    COUNTER
+}
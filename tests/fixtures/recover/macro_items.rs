@@ -0,0 +1,6 @@
+fn good(p: *const u8) -> u8 { unsafe { *p } }
+thread_local! { static X: u8 = 0; }
+fn boxed() -> Box<u8> {
+    let value = 1;
+    box value
+}
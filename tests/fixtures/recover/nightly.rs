@@ -0,0 +1,29 @@
+#![feature(box_syntax)]
+
+pub unsafe fn read(p: *const u8) -> u8 {
+    *p
+}
+
+// `box` expressions only ever parsed on nightly.
+pub fn boxed(p: *const u8) -> Box<u8> {
+    let value = unsafe { read(p) };
+    box value
+}
+
+pub struct Raw {
+    ptr: *const u8,
+}
+
+impl Raw {
+    pub fn first(&self) -> u8 {
+        unsafe { read(self.ptr) }
+    }
+}
+
+const PICK: u8 = if cfg!(unix) { 1 } else { 2 };
+
+static ONE: u8 = box 1;
+
+pub fn last(p: *const u8, len: usize) -> u8 {
+    unsafe { *p.add(len - 1) }
+}
@@ -0,0 +1,3 @@
+fn f(x: u8 @ y) {
+    let a = 1;
+}
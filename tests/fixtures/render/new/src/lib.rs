@@ -0,0 +1,10 @@
+pub fn f(p: *const u8) -> u8 {
+	let s = "日本語"; unsafe {
+        let a = *p;
+        let b = *p.add(1);
+        let c = *p.add(2);
+        let d = *p.add(3);
+        let e = *p.add(4);
+        a + b + c + d + e
+    }
+}
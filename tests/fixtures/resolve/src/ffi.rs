@@ -0,0 +1,4 @@
+extern "C" {
+    #[link_name = "abs"]
+    pub fn c_abs(x: i32) -> i32;
+}
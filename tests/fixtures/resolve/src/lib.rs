@@ -0,0 +1,18 @@
+mod ffi;
+mod util;
+
+use util::raw::{self, poke as write_byte};
+use util::Buffer;
+
+pub fn run(buf: &Buffer, p: *mut u8) -> u8 {
+    unsafe {
+        write_byte(p, 1);
+        raw::poke(p, 2);
+        crate::util::raw::clear(p);
+        let v = buf.get_unchecked(0);
+        let n = Buffer::capacity(buf);
+        ffi::c_abs(-1);
+        let s = String::from("x");
+        v + n as u8 + s.len() as u8
+    }
+}
@@ -0,0 +1,15 @@
+pub mod raw;
+
+pub struct Buffer {
+    data: Vec<u8>,
+}
+
+impl Buffer {
+    pub unsafe fn get_unchecked(&self, i: usize) -> u8 {
+        *self.data.as_ptr().add(i)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+}
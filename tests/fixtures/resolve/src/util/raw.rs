@@ -0,0 +1,7 @@
+pub unsafe fn poke(p: *mut u8, v: u8) {
+    *p = v;
+}
+
+pub fn clear(p: *mut u8) {
+    let _ = p;
+}
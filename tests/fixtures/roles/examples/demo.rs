@@ -0,0 +1,4 @@
+fn main() {
+    let x = 1u8;
+    println!("{}", roles::read(&x));
+}
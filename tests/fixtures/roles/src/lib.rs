@@ -0,0 +1,16 @@
+pub fn read(p: *const u8) -> u8 {
+    unsafe { *p }
+}
+
+#[test]
+fn reads_first_byte() {
+    let x = 1u8;
+    assert_eq!(unsafe { *(&x as *const u8) }, 1);
+}
+
+#[cfg(test)]
+mod tests {
+    fn helper(p: *const u8) -> u8 {
+        unsafe { *p }
+    }
+}
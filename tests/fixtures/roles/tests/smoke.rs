@@ -0,0 +1,5 @@
+#[test]
+fn smoke() {
+    let x = 1u8;
+    roles::read(&x);
+}
@@ -0,0 +1,9 @@
+fn outer(p: *const u8) -> u8 {
+    unsafe {
+        let read = || {
+            let x = *p;
+            x
+        };
+        read()
+    }
+}
@@ -0,0 +1,15 @@
+unsafe fn outer() -> usize {
+    const N: usize = {
+        let n = 4;
+        n
+    };
+    static S: usize = {
+        let s = 2;
+        s
+    };
+    let buf = [0u8; {
+        let m = N;
+        m
+    }];
+    buf.len() + S
+}
@@ -0,0 +1,9 @@
+fn outer(p: *const u8) -> u8 {
+    unsafe {
+        fn helper() -> u8 {
+            let x = 1;
+            x
+        }
+        *p + helper()
+    }
+}
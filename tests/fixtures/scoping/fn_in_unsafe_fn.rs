@@ -0,0 +1,7 @@
+unsafe fn outer(p: *const u8) -> u8 {
+    fn helper() -> u8 {
+        let x = 1;
+        x
+    }
+    *p + helper()
+}
@@ -0,0 +1 @@
+missing.rs
\ No newline at end of file
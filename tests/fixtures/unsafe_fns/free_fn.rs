@@ -0,0 +1,9 @@
+unsafe fn read(p: *const u8) -> u8 {
+    let x = *p;
+    x
+}
+
+fn one() -> u8 {
+    let y = 1;
+    y
+}
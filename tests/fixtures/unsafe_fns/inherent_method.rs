@@ -0,0 +1,12 @@
+struct Ptr(*const u8);
+
+impl Ptr {
+    unsafe fn read(&self) -> u8 {
+        let x = *self.0;
+        x
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+}
@@ -0,0 +1,12 @@
+struct Ptr(*const u8);
+
+impl Ptr {
+    fn read(&self) -> u8 {
+        unsafe fn inner(p: *const u8) -> u8 {
+            let x = *p;
+            x
+        }
+        let y = unsafe { inner(self.0) };
+        y
+    }
+}
@@ -0,0 +1,12 @@
+trait Reader {
+    fn ptr(&self) -> *const u8;
+
+    unsafe fn read(&self) -> u8 {
+        let p = self.ptr();
+        *p
+    }
+
+    fn is_null(&self) -> bool {
+        self.ptr().is_null()
+    }
+}
@@ -0,0 +1,12 @@
+trait Reader {
+    unsafe fn read(&self) -> u8;
+}
+
+struct Ptr(*const u8);
+
+impl Reader for Ptr {
+    unsafe fn read(&self) -> u8 {
+        let x = *self.0;
+        x
+    }
+}
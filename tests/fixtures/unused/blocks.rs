@@ -0,0 +1,49 @@
+static mut COUNTER: u32 = 0;
+
+pub fn empty_block(x: u32) -> u32 {
+    unsafe { x + 1 }
+}
+
+pub fn used_block(p: *const u32) -> u32 {
+    unsafe { *p }
+}
+
+pub fn nested_block(p: *const u32) -> u32 {
+    unsafe {
+        let x = *p;
+        unsafe { COUNTER += x };
+        x
+    }
+}
+
+pub unsafe fn empty_fn(len: usize) -> usize {
+    len * 2
+}
+
+pub unsafe fn block_in_unsafe_fn(p: *const u32) -> u32 {
+    unsafe { *p }
+}
+
+pub fn unknown_call(x: u32) -> u32 {
+    unsafe { external(x) }
+}
+
+pub trait Reset {
+    unsafe fn reset(&mut self);
+}
+
+extern "system" {
+    fn GetLastError() -> u32;
+}
+
+pub fn last_error() -> u32 {
+    unsafe { GetLastError() }
+}
+
+pub struct Counter(u32);
+
+impl Reset for Counter {
+    unsafe fn reset(&mut self) {
+        self.0 = 0;
+    }
+}
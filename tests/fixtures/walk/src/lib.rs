@@ -0,0 +1,6 @@
+mod nested;
+mod util;
+
+pub fn read(p: *const u8) -> u8 {
+    unsafe { *p }
+}
@@ -0,0 +1,3 @@
+pub fn len(v: &[u8]) -> usize {
+    v.len()
+}
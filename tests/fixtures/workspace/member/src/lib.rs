@@ -0,0 +1,4 @@
+pub unsafe fn read(p: *const u8) -> u8 {
+    let x = unsafe { *p };
+    x
+}